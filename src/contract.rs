@@ -1,19 +1,32 @@
 use cosmwasm_std::{
-    entry_point, to_binary,   CosmosMsg, Deps, DepsMut,Binary,
-    Env, MessageInfo, Response, StdResult, Uint128, WasmMsg,BankMsg,Coin, Decimal
+    entry_point, to_binary, Addr, ContractInfoResponse, QueryRequest, WasmQuery,   CosmosMsg, Deps, DepsMut,Binary,
+    Env, MessageInfo, Order, Reply, Response, StdError, StdResult, Storage, SubMsg, Uint128, WasmMsg,BankMsg,Coin, Decimal
 };
 
 use crate::error::ContractError;
-use crate::msg::{ExecuteMsg,Image, InstantiateMsg, QueryMsg, WhiteUserInfo};
+use crate::msg::{ExecuteMsg,Metadata,Trait, InstantiateMsg, QueryMsg, WhiteUserInfo, CollectionInfoResponse, TokenUriResponse, ProvenanceResponse, CollectionSummary, CollectionsResponse, WhiteUsersResponse, FreeMintersResponse, MerkleProof, PendingPayout, PendingPayoutsResponse, BalanceInfo, BalancesResponse};
 use crate::state::{
-    CONFIG,ADMININFO,State, AdminInfo, USERINFO,COLLECTIONINFO, CollectionInfo, FREEMINTER, WHITEUSERS
+    CONFIG,ADMININFO,State, AdminInfo, USERINFO,COLLECTIONINFO, CollectionInfo, FREEMINTER, WHITEUSERS, MintStatus, SalePhase, ENTROPY, TOKENPOOL, TOKENTRAITS, AIRDROPS, AIRDROPQUEUE, AirdropQueue, PAYOUTS, OWEDPAYOUTS, PHASEUSERINFO, PHASEMINTCOUNT, PENDINGCOLLECTION, PendingCollection
 };
-use crate::rand::{sha_256, Prng};
+use crate::rand::{random_below, sha_256, Prng};
+use crate::merkle::{is_hex_digest, leaf_hash, verify_proof};
 
-use cw721_base::{ExecuteMsg as Cw721BaseExecuteMsg, MintMsg};
-use rand::{RngCore, SeedableRng};
+use cw721::NumTokensResponse;
+use cw721_base::{ExecuteMsg as Cw721BaseExecuteMsg, InstantiateMsg as Cw721InstantiateMsg, QueryMsg as Cw721QueryMsg, MintMsg, MinterResponse};
+use cw_storage_plus::Bound;
+use rand::SeedableRng;
 use rand_chacha::ChaChaRng;
-
+use std::collections::HashSet;
+
+// settings for pagination
+const MAX_LIMIT: u32 = 30;
+const DEFAULT_LIMIT: u32 = 10;
+// tokens minted per Airdrop call, the rest stays queued
+const AIRDROP_BATCH: u32 = 50;
+// tokens per UploadTraits batch
+const MAX_TRAIT_BATCH: usize = 100;
+// reply to the cw721 instantiation started by InstantiateCollection
+const INSTANTIATE_CW721_REPLY_ID: u64 = 1;
 
 #[entry_point]
 pub fn instantiate(
@@ -23,7 +36,10 @@ pub fn instantiate(
     msg: InstantiateMsg,
 ) -> Result<Response, ContractError> {
     let state = State {
+        treasury:msg.owner.clone(),
         owner:msg.owner,
+        listing_fee:None,
+        platform_fee_bps:0
     };
     CONFIG.save(deps.storage, &state)?;
     Ok(Response::default())
@@ -37,14 +53,30 @@ pub fn execute(
     msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
     match msg {
-        ExecuteMsg::Mint{address} => execute_mint(deps, env, info,address),
+        ExecuteMsg::Mint{address,quantity,proof} => execute_mint(deps, env, info,address,quantity,proof),
         ExecuteMsg::ChangeOwner { address } => execute_chage_owner(deps, info, address),
-        ExecuteMsg::AddCollection { members, nft_address,collection}  => execute_add_collection(deps, info,members, nft_address,collection),
+        ExecuteMsg::AddCollection { members, nft_address,collection,token_ids}  => execute_add_collection(deps, env, info,members, nft_address,collection,token_ids),
+        ExecuteMsg::InstantiateCollection { members, collection, token_ids, code_id, name, symbol } => execute_instantiate_collection(deps, env, info, members, collection, token_ids, code_id, name, symbol),
+        ExecuteMsg::SetListingFee { fee } => execute_set_listing_fee(deps, info, fee),
+        ExecuteMsg::SetTreasury { address } => execute_set_treasury(deps, info, address),
+        ExecuteMsg::SetPlatformFee { fee_bps } => execute_set_platform_fee(deps, info, fee_bps),
+        ExecuteMsg::Withdraw { nft_address } => execute_withdraw(deps, info, Some(nft_address)),
+        ExecuteMsg::WithdrawAll {} => execute_withdraw(deps, info, None),
+        ExecuteMsg::SweepDust {} => execute_sweep_dust(deps, env, info),
+        ExecuteMsg::UploadTraits { nft_address, traits } => execute_upload_traits(deps, info, nft_address, traits),
+        ExecuteMsg::ApproveCollection { nft_address } => execute_set_listed(deps, info, nft_address, true),
+        ExecuteMsg::DelistCollection { nft_address } => execute_set_listed(deps, info, nft_address, false),
+        ExecuteMsg::RejectCollection { nft_address } => execute_reject_collection(deps, info, nft_address),
         ExecuteMsg::UpdateCollection { members, nft_address,collection}  => execute_update_collection(deps, info,members, nft_address,collection),
         ExecuteMsg::SetMintFlag { address, time } => execute_set_flag(deps, info, address,time),
         ExecuteMsg::AddFreeMinter { address, minters } => execute_free_minter(deps, info, address,minters),
-        ExecuteMsg::SwitchSaleType { address, public_mint, private_mint, free_mint } => execute_switch_type(deps, info, address,public_mint,private_mint,free_mint),
-        ExecuteMsg::AddWhiteUsers { address, white_users } => execute_add_white_user(deps, info, address,white_users)
+        ExecuteMsg::AddWhiteUsers { address, white_users } => execute_add_white_user(deps, info, address,white_users),
+        ExecuteMsg::Reveal { address } => execute_reveal(deps, env, info, address),
+        ExecuteMsg::RemoveWhiteUsers { address, white_users } => execute_remove_white_user(deps, info, address,white_users),
+        ExecuteMsg::RemoveFreeMinters { address, minters } => execute_remove_free_minter(deps, info, address,minters),
+        ExecuteMsg::SetMerkleRoot { address, phase, root } => execute_set_merkle_root(deps, info, address,phase,root),
+        ExecuteMsg::MintReserved { nft_address, recipient, quantity } => execute_mint_reserved(deps, env, info, nft_address,recipient,quantity),
+        ExecuteMsg::Airdrop { nft_address, recipients } => execute_airdrop(deps, env, info, nft_address,recipients)
     }                                  
 }
 
@@ -52,264 +84,476 @@ fn execute_mint(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
-    address:String
+    address:String,
+    quantity:Option<u32>,
+    proof:Option<MerkleProof>
 ) -> Result<Response, ContractError> {
     //address check
     deps.api.addr_validate(&address)?;
     let sender = info.sender.to_string();
 
+    let quantity = quantity.unwrap_or(1);
+    if quantity == 0 {
+        return Err(ContractError::ZeorError {  });
+    }
+    let mint_quantity = Uint128::from(quantity);
+
     let collection_info = COLLECTIONINFO.may_load(deps.storage, &address)?;
 
-    if collection_info == None{
+    if collection_info.is_none(){
         return Err(ContractError::CollectionNotFound { });
     }
 
-    let  collection_info = collection_info.unwrap();
+    let mut collection_info = collection_info.unwrap();
 
-    if collection_info.start_mint_time>env.block.time.seconds(){
-        return Err(ContractError::MintNotStarted {  });
+    if !collection_info.can_mint {
+        return Err(ContractError::CollectionNotApproved {  });
     }
 
-    if collection_info.mint_count >= collection_info.total_nft {
+    let phase = match mint_phase(&collection_info, env.block.time.seconds()) {
+        (MintStatus::NotStarted, _, _) => return Err(ContractError::MintNotStarted {  }),
+        (MintStatus::Ended, _, _) | (_, None, _) => return Err(ContractError::MintEnded {}),
+        (MintStatus::Active, Some(phase), _) => phase.clone()
+    };
+
+    // the reserved allocation and queued airdrops are kept out of the sale
+    let sale_supply = collection_info.total_nft - collection_info.reserved;
+    let sale_count = collection_info.mint_count - collection_info.reserved_count + airdrop_pending(deps.storage, &address)?;
+
+    if sale_count >= sale_supply {
         return Err(ContractError::MintEnded {});
     }
 
+    if sale_count + mint_quantity > sale_supply {
+        return Err(ContractError::MintExceeded {  });
+    }
 
-    if !collection_info.free_mint  {
-        if collection_info.public_mint
-            {
-                let count = USERINFO.may_load(deps.storage,(&address,&sender))?;
-            
-                let price = collection_info.public_price;
+    let free_minter = FREEMINTER.may_load(deps.storage, (&address,&sender))?.unwrap_or(false);
 
-                let free_minter = FREEMINTER.may_load(deps.storage, (&address,&sender))?; 
+    // nothing is written before every check below has passed
+    let limit = check_mint_limit(deps.as_ref(), &collection_info, &address, &sender, &phase, free_minter, mint_quantity, proof)?;
 
-                if count == None{
-                    USERINFO.save(deps.storage,(&address,&sender), &Uint128::new(1))?;
-                }
+    let price = mint_price(&phase, free_minter) * mint_quantity;
 
-                else {
-                    let count = count.unwrap() + Uint128::new(1);
-                    USERINFO.save(deps.storage,(&address,&sender), &count)?;
-                    if count > collection_info.max_nft && free_minter == None{
-                        return Err(ContractError::MintExceeded {  })
-                    }
-                }
+    let refunds = check_payment(&info.funds, &phase.denom, price)?;
 
-                if free_minter == None
-                    {
-                        let amount= info
-                            .funds
-                            .iter()
-                            .find(|c| c.denom == collection_info.denom)
-                            .map(|c| Uint128::from(c.amount))
-                            .unwrap_or_else(Uint128::zero);
-                
-                        if amount != price{
-                            return Err(ContractError::Notenough {});
-                        }
-                    }
+    USERINFO.save(deps.storage,(&address,&sender), &limit.wallet)?;
+    PHASEUSERINFO.save(deps.storage,(&address,&phase.name,&sender), &limit.phase_wallet)?;
+    PHASEMINTCOUNT.save(deps.storage,(&address,&phase.name), &limit.phase_supply)?;
+    if let Some(count) = limit.whitelist {
+        WHITEUSERS.save(deps.storage,(&address,&sender), &count)?;
+    }
 
-                let mut check_mint = collection_info.check_mint;
-
-                let count = check_mint.len();
-                
-                let prng_seed: Vec<u8> = sha_256(base64::encode("entropy").as_bytes()).to_vec();
-                let random_seed = new_entropy(&info,&env, prng_seed.as_ref(), prng_seed.as_ref());
-                let mut rng = ChaChaRng::from_seed(random_seed);
-                let  rand_num = (rng.next_u32() % (count as u32)) as usize ;
-                let rand = check_mint[rand_num];
-                check_mint.remove(rand_num);
-            
-                let token_id = [collection_info.name,rand.to_string()].join(".");
-            
-                COLLECTIONINFO.update(deps.storage, &address,|collection_info|->StdResult<_>{
-                    let mut collection_info = collection_info.unwrap();    
-                    collection_info.mint_count = collection_info.mint_count+Uint128::new(1);
-                    collection_info.check_mint = check_mint;
-                    Ok(collection_info)
-                })?;
-
-                let admins = ADMININFO.load(deps.storage,&address)?;
-                let mut messages:Vec<CosmosMsg> = vec![];
-                messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
-                        contract_addr: address,
-                        msg: to_binary(&Cw721BaseExecuteMsg::Mint(MintMsg {
-                            //::<Metadata>
-                            token_id: token_id.clone(),
-                            owner: sender,
-                            token_uri: Some([[collection_info.url,rand.to_string()].join(""),"json".to_string()].join(".")),
-                            extension:  Image{
-                                image:Some([[collection_info.image_url,rand.to_string()].join(""),"png".to_string()].join("."))
-                            }
-                        }))?,
-                        funds: vec![],
-                    }));
-
-                if free_minter == None{
-                    for admin in admins {
-                        messages.push(CosmosMsg::Bank(BankMsg::Send {
-                                to_address: admin.address,
-                                amount:vec![Coin{
-                                    denom:collection_info.denom.clone(),
-                                    amount:admin.portion * price
-                                }]
-                        }));
-                    }
-                }
-            
+    let mut messages = mint_tokens(deps.storage, &info, &env, &address, &mut collection_info, &sender, quantity)?;
+    COLLECTIONINFO.save(deps.storage, &address, &collection_info)?;
+
+    // the platform takes its cut before the admins split the rest
+    let state = CONFIG.load(deps.storage)?;
+    let platform_fee = price.multiply_ratio(state.platform_fee_bps, 10_000u128);
+    if !platform_fee.is_zero() {
+        messages.push(CosmosMsg::Bank(BankMsg::Send {
+            to_address: state.treasury,
+            amount: vec![Coin{denom:phase.denom.clone(),amount:platform_fee}]
+        }));
+    }
+    messages.extend(distribute_proceeds(deps.storage, &address, &collection_info, &phase.denom, price - platform_fee)?);
+
+    if !refunds.is_empty(){
+        messages.push(CosmosMsg::Bank(BankMsg::Send {
+            to_address: sender,
+            amount: refunds
+        }));
+    }
+
+    Ok(Response::new()
+        .add_attribute("phase", phase.name)
+        .add_attribute("platform_fee", Coin{denom:phase.denom,amount:platform_fee}.to_string())
+        .add_messages(messages)
+    )
+}
+
+/// Draws `quantity` tokens for `owner`, counts them as minted on `collection_info` and returns
+/// the cw721 mint messages. The caller saves `collection_info`.
+#[allow(clippy::too_many_arguments)]
+fn mint_tokens(
+    storage: &mut dyn Storage,
+    info: &MessageInfo,
+    env: &Env,
+    address: &str,
+    collection_info: &mut CollectionInfo,
+    owner: &str,
+    quantity: u32
+) -> StdResult<Vec<CosmosMsg>> {
+    let remaining = (collection_info.total_nft - collection_info.mint_count).u128() as u32;
+    let rands = draw_tokens(storage, info, env, address, remaining, quantity)?;
+    collection_info.mint_count += Uint128::from(quantity);
+
+    let mut messages:Vec<CosmosMsg> = vec![];
+    for rand in rands {
+        let token_id = token_id(collection_info, rand);
+        let uri = token_uri(collection_info, rand);
+        let attributes = match metadata_number(collection_info, rand) {
+            Some(number) => TOKENTRAITS.may_load(storage, (address, number))?,
+            None => None
+        };
+        messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: address.to_string(),
+                msg: to_binary(&Cw721BaseExecuteMsg::Mint(MintMsg {
+                    token_id,
+                    owner: owner.to_string(),
+                    token_uri: Some(uri.token_uri),
+                    extension: token_metadata(collection_info, rand, uri.image, attributes)
+                }))?,
+                funds: vec![],
+            }));
+    }
+    Ok(messages)
+}
+
+/// Counters a mint is charged against, holding the values to store once the mint goes through.
+struct MintLimit {
+    wallet: Uint128,
+    phase_wallet: Uint128,
+    phase_supply: Uint128,
+    whitelist: Option<Uint128>
+}
 
-                Ok(Response::new()
-                    .add_messages(messages)
-                )
+/// Every mint counts against the collection wide `max_nft` and the phase's `max_per_wallet`,
+/// which free minters are exempt from, and against the phase's `max_supply`. When the phase
+/// has a merkle root, a valid proof grants the allocation it commits to and caps what the
+/// sender mints during that phase alone; whitelist phases need the proof, the others let free
+/// minters in without one. Whitelist phases without a root draw from the `AddWhiteUsers` list.
+#[allow(clippy::too_many_arguments)]
+fn check_mint_limit(
+    deps: Deps,
+    collection_info: &CollectionInfo,
+    address: &str,
+    sender: &str,
+    phase: &SalePhase,
+    free_minter: bool,
+    quantity: Uint128,
+    proof: Option<MerkleProof>
+) -> Result<MintLimit, ContractError> {
+    let allocation = match (&phase.merkle_root, proof) {
+        (Some(root), Some(proof)) => {
+            if !verify_proof(root, &proof.proof, leaf_hash(sender, proof.allocation.u128())){
+                return Err(ContractError::InvalidProof {  });
             }
-        else {
-                let count = WHITEUSERS.may_load(deps.storage,(&address,&sender))?;
-                if count == None{
-                    return Err(ContractError::NOTWHITEUSERS {  });
-                }
-                else {
-                    let count = count.unwrap();
-                    if count == Uint128::new(0){
-                        return Err(ContractError::MintExceeded {  });
-                    } 
-                    else  {
-                        WHITEUSERS.update(deps.storage, (&address,&sender), 
-                        |count|->StdResult<_>{
-                            let mut count = count.unwrap();
-                            count = count - Uint128::new(1);
-                            Ok(count)
-                      })?; 
-                    }
-                }
-                
-                let price = collection_info.private_price;
-
-                let free_minter = FREEMINTER.may_load(deps.storage, (&address,&sender))?; 
-
-                if free_minter == None
-                    {
-                        let amount= info
-                            .funds
-                            .iter()
-                            .find(|c| c.denom == collection_info.denom)
-                            .map(|c| Uint128::from(c.amount))
-                            .unwrap_or_else(Uint128::zero);
-                
-                        if amount != price{
-                            return Err(ContractError::Notenough {});
-                        }
-                    }
+            Some(proof.allocation)
+        },
+        _ => None
+    };
 
-                let mut check_mint = collection_info.check_mint;
-
-                let count = check_mint.len();
-                
-                let prng_seed: Vec<u8> = sha_256(base64::encode("entropy").as_bytes()).to_vec();
-                let random_seed = new_entropy(&info,&env, prng_seed.as_ref(), prng_seed.as_ref());
-                let mut rng = ChaChaRng::from_seed(random_seed);
-                let  rand_num = (rng.next_u32() % (count as u32)) as usize ;
-                let rand = check_mint[rand_num];
-                check_mint.remove(rand_num);
-            
-                let token_id = [collection_info.name,rand.to_string()].join(".");
-            
-                COLLECTIONINFO.update(deps.storage, &address,|collection_info|->StdResult<_>{
-                    let mut collection_info = collection_info.unwrap();    
-                    collection_info.mint_count = collection_info.mint_count+Uint128::new(1);
-                    collection_info.check_mint = check_mint;
-                    Ok(collection_info)
-                })?;
-
-                let admins = ADMININFO.load(deps.storage,&address)?;
-                let mut messages:Vec<CosmosMsg> = vec![];
-                messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
-                        contract_addr: address,
-                        msg: to_binary(&Cw721BaseExecuteMsg::Mint(MintMsg {
-                            //::<Metadata>
-                            token_id: token_id.clone(),
-                            owner: sender,
-                            token_uri: Some([[collection_info.url,rand.to_string()].join(""),"json".to_string()].join(".")),
-                            extension:  Image{
-                                image:Some([[collection_info.image_url,rand.to_string()].join(""),"png".to_string()].join("."))
-                            }
-                        }))?,
-                        funds: vec![],
-                    }));
-
-                if free_minter == None{
-                    for admin in admins {
-                        messages.push(CosmosMsg::Bank(BankMsg::Send {
-                                to_address: admin.address,
-                                amount:vec![Coin{
-                                    denom:collection_info.denom.clone(),
-                                    amount:admin.portion * price
-                                }]
-                        }));
-                    }
-                }
-            
+    let wallet = USERINFO.may_load(deps.storage,(address,sender))?.unwrap_or_default() + quantity;
+    let phase_wallet = PHASEUSERINFO.may_load(deps.storage,(address,&phase.name,sender))?.unwrap_or_default() + quantity;
 
-                Ok(Response::new()
-                    .add_messages(messages)
-                )
+    let whitelist = if phase.merkle_root.is_some() {
+        if allocation.is_none() && (phase.whitelist || !free_minter) {
+            return Err(ContractError::NotWhiteUsers {  });
+        }
+        if allocation.is_some_and(|allocation| phase_wallet > allocation) {
+            return Err(ContractError::MintExceeded {  });
+        }
+        None
+    }
+    else if phase.whitelist {
+        match WHITEUSERS.may_load(deps.storage,(address,sender))? {
+            None => return Err(ContractError::NotWhiteUsers {  }),
+            Some(count) if count < quantity => return Err(ContractError::MintExceeded {  }),
+            Some(count) => Some(count - quantity)
         }
     }
+    else {
+        None
+    };
 
-    else{
-        let count = USERINFO.may_load(deps.storage,(&address,&sender))?;
-        let free_minter = FREEMINTER.may_load(deps.storage, (&address,&sender))?; 
-    
-        if count == None{
-            USERINFO.save(deps.storage,(&address,&sender), &Uint128::new(1))?;
+    if !free_minter {
+        let phase_max = phase.max_per_wallet.unwrap_or(collection_info.max_nft);
+        if wallet > collection_info.max_nft || phase_wallet > phase_max {
+            return Err(ContractError::MintExceeded {  });
         }
+    }
 
-        else {
-            let count = count.unwrap() + Uint128::new(1);
-            USERINFO.save(deps.storage,(&address,&sender), &count)?;
-            if count > collection_info.max_nft && free_minter == None{
-                return Err(ContractError::MintExceeded {  })
-            }
+    let phase_supply = PHASEMINTCOUNT.may_load(deps.storage,(address,&phase.name))?.unwrap_or_default() + quantity;
+    if let Some(max_supply) = phase.max_supply {
+        if phase_supply > max_supply {
+            return Err(ContractError::PhaseSoldOut { name: phase.name.clone() });
         }
+    }
 
-        let mut check_mint = collection_info.check_mint;
+    Ok(MintLimit{
+        wallet,
+        phase_wallet,
+        phase_supply,
+        whitelist
+    })
+}
 
-        let count = check_mint.len();
-        
-        let prng_seed: Vec<u8> = sha_256(base64::encode("entropy").as_bytes()).to_vec();
-        let random_seed = new_entropy(&info,&env, prng_seed.as_ref(), prng_seed.as_ref());
-        let mut rng = ChaChaRng::from_seed(random_seed);
-        let  rand_num = (rng.next_u32() % (count as u32)) as usize ;
-        let rand = check_mint[rand_num];
-        check_mint.remove(rand_num);
-    
-        let token_id = [collection_info.name,rand.to_string()].join(".");
-    
-        COLLECTIONINFO.update(deps.storage, &address,|collection_info|->StdResult<_>{
-            let mut collection_info = collection_info.unwrap();    
-            collection_info.mint_count = collection_info.mint_count+Uint128::new(1);
-            collection_info.check_mint = check_mint;
-            Ok(collection_info)
+/// Unit price the sender pays in the given phase.
+pub fn mint_price(phase:&SalePhase, free_minter:bool) -> Uint128 {
+    if free_minter {
+        return Uint128::zero();
+    }
+    phase.price
+}
+
+/// Splits `amount` between the collection admins. In auto payout mode every admin is sent
+/// their share right away, otherwise the shares accrue until the admins withdraw them.
+fn distribute_proceeds(storage:&mut dyn Storage, address:&str, collection_info:&CollectionInfo, denom:&str, amount:Uint128) -> StdResult<Vec<CosmosMsg>> {
+    if amount.is_zero(){
+        return Ok(vec![]);
+    }
+    let shares = split_shares(&ADMININFO.load(storage,address)?, amount);
+    if collection_info.auto_payout {
+        return Ok(shares.into_iter().filter(|(_, share)| !share.is_zero()).map(|(admin, share)| CosmosMsg::Bank(BankMsg::Send {
+                to_address: admin,
+                amount:vec![Coin{
+                    denom:denom.to_string(),
+                    amount:share
+                }]
+        })).collect());
+    }
+    for (admin, share) in shares.into_iter().filter(|(_, share)| !share.is_zero()) {
+        PAYOUTS.update(storage, (&admin, address, denom), |pending| -> StdResult<_> {
+            Ok(pending.unwrap_or_default() + share)
         })?;
+    }
+    OWEDPAYOUTS.update(storage, denom, |owed| -> StdResult<_> {
+        Ok(owed.unwrap_or_default() + amount)
+    })?;
+    Ok(vec![])
+}
 
-        Ok(Response::new()
-            .add_message(CosmosMsg::Wasm(WasmMsg::Execute {
-                contract_addr: address,
-                msg: to_binary(&Cw721BaseExecuteMsg::Mint(MintMsg {
-                    //::<Metadata>
-                    token_id: token_id.clone(),
-                    owner: sender,
-                    token_uri: Some([[collection_info.url,rand.to_string()].join(""),"json".to_string()].join(".")),
-                    extension:  Image{
-                        image:Some([[collection_info.image_url,rand.to_string()].join(""),"png".to_string()].join("."))
+/// Each admin's share of `amount`. Shares are rounded down and whatever rounding leaves
+/// over goes to the first admin, so the shares always add up to `amount`.
+pub fn split_shares(admins:&[AdminInfo], amount:Uint128) -> Vec<(String, Uint128)> {
+    let mut shares:Vec<(String, Uint128)> = admins.iter().map(|admin| (admin.address.clone(), admin.portion * amount)).collect();
+    let distributed:Uint128 = shares.iter().map(|(_, share)| *share).sum();
+    if let Some((_, share)) = shares.first_mut() {
+        *share += amount - distributed;
+    }
+    shares
+}
+
+/// Metadata location of a token number. Hidden collections point every token at the
+/// placeholder, rendered with the token number, until `Reveal` fixes the offset, after which
+/// token `n` resolves to metadata number `(n - 1 + offset) % total_nft + 1`. Tokens keep the
+/// placeholder uri they were minted with, so for them this is the source of truth.
+pub fn token_uri(collection_info:&CollectionInfo, token_number:u32) -> TokenUriResponse {
+    let number = match metadata_number(collection_info, token_number) {
+        Some(number) => number,
+        None => {
+            return TokenUriResponse{
+                token_uri:collection_info.placeholder_url.as_ref().map(|placeholder| render_template(placeholder, collection_info, token_number)).unwrap_or_default(),
+                image:None
+            }
+        }
+    };
+    match &collection_info.templates {
+        Some(templates) => TokenUriResponse{
+            token_uri:render_template(&templates.token_uri, collection_info, number),
+            image:templates.image.as_ref().map(|image| render_template(image, collection_info, number))
+        },
+        None => TokenUriResponse{
+            token_uri:[[collection_info.url.clone(),number.to_string()].join(""),"json".to_string()].join("."),
+            image:Some([[collection_info.image_url.clone(),number.to_string()].join(""),"png".to_string()].join("."))
+        }
+    }
+}
+
+pub fn token_id(collection_info:&CollectionInfo, token_number:u32) -> String {
+    match &collection_info.templates {
+        Some(templates) => render_template(&templates.token_id, collection_info, token_number),
+        None => [collection_info.name.clone(),token_number.to_string()].join(".")
+    }
+}
+
+const TEMPLATE_FIELDS: [&str; 4] = ["{name}", "{url}", "{image_url}", "{n}"];
+
+/// Fills a template with the collection settings and the zero padded token number.
+pub fn render_template(template:&str, collection_info:&CollectionInfo, token_number:u32) -> String {
+    let padding = collection_info.templates.as_ref().and_then(|templates| templates.padding).unwrap_or(0) as usize;
+    template
+        .replace("{name}", &collection_info.name)
+        .replace("{url}", &collection_info.url)
+        .replace("{image_url}", &collection_info.image_url)
+        .replace("{n}", &format!("{:0width$}", token_number, width = padding))
+}
+
+/// Token ids and locations have to differ per token, so their templates need `{n}`, and
+/// anything in braces must be a known field. The placeholder may leave out `{n}`.
+fn check_templates(collection:&CollectionInfo) -> Result<(), ContractError> {
+    let invalid = |template:&str| ContractError::InvalidTemplate { template: template.to_string() };
+    if let Some(placeholder) = &collection.placeholder_url {
+        if !known_fields(placeholder) {
+            return Err(invalid(placeholder));
+        }
+    }
+    let templates = match &collection.templates {
+        Some(templates) => templates,
+        None => return Ok(())
+    };
+    if templates.padding.unwrap_or(0) > 10 {
+        return Err(invalid("padding cannot exceed 10 digits"));
+    }
+    for template in [Some(&templates.token_id), Some(&templates.token_uri), templates.image.as_ref()].iter().flatten() {
+        if !template.contains("{n}") || !known_fields(template) {
+            return Err(invalid(template));
+        }
+    }
+    Ok(())
+}
+
+/// Whether every brace pair in `template` is one of `TEMPLATE_FIELDS`.
+fn known_fields(template:&str) -> bool {
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let end = match rest[start..].find('}') {
+            Some(end) => start + end + 1,
+            None => return false
+        };
+        if !TEMPLATE_FIELDS.contains(&&rest[start..end]) {
+            return false;
+        }
+        rest = &rest[end..];
+    }
+    true
+}
+
+/// Metadata number a token resolves to, `None` while a hidden collection is not revealed.
+pub fn metadata_number(collection_info:&CollectionInfo, token_number:u32) -> Option<u32> {
+    match (&collection_info.placeholder_url, collection_info.reveal_offset) {
+        (Some(_), None) => None,
+        (Some(_), Some(offset)) => {
+            let total = collection_info.total_nft.u128() as u64;
+            Some(((token_number as u64 - 1 + offset as u64) % total + 1) as u32)
+        },
+        _ => Some(token_number)
+    }
+}
+
+/// Token numbers start at one. Hidden collections are numbered `1..=total_nft`, which is the
+/// range `Reveal` shifts; other collections may have been registered with their own ids.
+fn check_token_number(collection_info:&CollectionInfo, token_number:u32) -> StdResult<()> {
+    let hidden = collection_info.placeholder_url.is_some();
+    if token_number == 0 || (hidden && Uint128::from(token_number) > collection_info.total_nft) {
+        return Err(StdError::generic_err(format!("Token number {} is not in the collection", token_number)));
+    }
+    Ok(())
+}
+
+/// On-chain extension of a token, filled from the collection's metadata template and royalty.
+pub fn token_metadata(collection_info:&CollectionInfo, token_number:u32, image:Option<String>, attributes:Option<Vec<Trait>>) -> Metadata {
+    let template = collection_info.metadata.clone().unwrap_or_default();
+    let fill = |field:Option<String>| field.map(|field| render_template(&field, collection_info, token_number));
+    Metadata{
+        image,
+        name:fill(template.name),
+        description:fill(template.description),
+        external_url:fill(template.external_url),
+        animation_url:fill(template.animation_url),
+        background_color:template.background_color,
+        attributes,
+        royalty_percentage:collection_info.royalty.as_ref().map(|royalty| royalty.percentage),
+        royalty_payment_address:collection_info.royalty.as_ref().map(|royalty| royalty.payment_address.clone()),
+        ..Metadata::default()
+    }
+}
+
+/// Checks that `funds` cover `price` in `denom` and returns the coins to send back:
+/// any excess of the payment denom plus every unrelated coin.
+fn check_payment(funds:&[Coin], denom:&str, price:Uint128) -> Result<Vec<Coin>, ContractError> {
+    if price.is_zero(){
+        return Ok(funds.to_vec());
+    }
+
+    let amount = funds
+        .iter()
+        .find(|c| c.denom == denom)
+        .map(|c| c.amount);
+
+    match amount {
+        None if !funds.is_empty() => Err(ContractError::WrongDenom {
+            expected: Coin{denom:denom.to_string(),amount:price}.to_string(),
+            received: funds.iter().map(|c| c.to_string()).collect::<Vec<String>>().join(",")
+        }),
+        Some(amount) if amount >= price => {
+            let mut refunds = vec![];
+            for coin in funds {
+                if coin.denom == denom {
+                    if amount > price {
+                        refunds.push(Coin{denom:coin.denom.clone(),amount:amount - price});
                     }
-                }))?,
-                funds: vec![],
-            }))
-        )
+                }
+                else if !coin.amount.is_zero() {
+                    refunds.push(coin.clone());
+                }
+            }
+            Ok(refunds)
+        },
+        _ => Err(ContractError::Notenough {})
     }
 }
 
+/// Picks `quantity` token numbers out of the `remaining` ones left in the collection's pool.
+/// The randomness mixes the collection's rolling entropy, which every mint advances,
+/// with the block, transaction and sender so the outcome cannot be precomputed from a constant seed.
+fn draw_tokens(storage:&mut dyn Storage, info:&MessageInfo, env:&Env, address:&str, remaining:u32, quantity:u32) -> StdResult<Vec<u32>> {
+    let prng_seed = ENTROPY.may_load(storage, address)?.unwrap_or_default();
+    let random_seed = new_entropy(info,env, prng_seed.as_ref(), address.as_bytes());
+    let mut rng = ChaChaRng::from_seed(random_seed);
+
+    let mut rands = vec![];
+    for drawn in 0..quantity {
+        let remaining = remaining - drawn;
+        let  rand_num = random_below(&mut rng, remaining);
+        rands.push(take_token(storage, address, rand_num, remaining)?);
+    }
+
+    ENTROPY.save(storage, address, &sha_256(&[prng_seed.as_slice(), &random_seed].concat()).to_vec())?;
+    Ok(rands)
+}
+
+/// Removes the token at `index` from a pool of `remaining` tokens by moving the last one into its slot.
+fn take_token(storage:&mut dyn Storage, address:&str, index:u32, remaining:u32) -> StdResult<u32> {
+    let last = remaining - 1;
+    let token = TOKENPOOL.may_load(storage, (address, index))?.unwrap_or(index + 1);
+    let last_token = TOKENPOOL.may_load(storage, (address, last))?.unwrap_or(last + 1);
+
+    if index != last {
+        TOKENPOOL.save(storage, (address, index), &last_token)?;
+    }
+    TOKENPOOL.remove(storage, (address, last));
+    Ok(token)
+}
+
+/// Whether the pool still holds exactly the tokens `1..=total_nft`, in any order.
+fn pool_is_range(storage:&dyn Storage, address:&str) -> StdResult<bool> {
+    let mut indexes = vec![];
+    let mut tokens = vec![];
+    for entry in TOKENPOOL.prefix(address).range(storage, None, None, Order::Ascending) {
+        let (index, token) = entry?;
+        indexes.push(index + 1);
+        tokens.push(token);
+    }
+    tokens.sort_unstable();
+    Ok(indexes == tokens)
+}
+
+
+/// Loads a collection for a management action, which its creator or the contract owner may perform.
+fn load_managed_collection(storage:&dyn Storage, sender:&Addr, address:&str) -> Result<CollectionInfo, ContractError> {
+    let collection_info = COLLECTIONINFO.may_load(storage, address)?;
+    if collection_info.is_none(){
+        return Err(ContractError::CollectionNotFound {  })
+    }
+    let collection_info = collection_info.unwrap();
+    let state = CONFIG.load(storage)?;
+    if state.owner != *sender && collection_info.creator != *sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    Ok(collection_info)
+}
 
 fn execute_chage_owner(
     deps: DepsMut,
@@ -317,7 +561,7 @@ fn execute_chage_owner(
     address: String,
 ) -> Result<Response, ContractError> {
    let state =CONFIG.load(deps.storage)?;
-    if state.owner != info.sender.to_string() {
+    if state.owner != info.sender {
         return Err(ContractError::Unauthorized {});
     }
 
@@ -337,18 +581,18 @@ fn execute_set_flag(
     address: String,
     time:u64
 ) -> Result<Response, ContractError> {
-   let state =CONFIG.load(deps.storage)?;
-    if state.owner != info.sender.to_string() {
-        return Err(ContractError::Unauthorized {});
-    }
-    let collection_info = COLLECTIONINFO.may_load(deps.storage, &address)?;
-    if collection_info == None{
-        return Err(ContractError::CollectionNotFound {  })
-    }
+    load_managed_collection(deps.storage, &info.sender, &address)?;
+    // the whole schedule moves so that its first phase opens at `time`
     COLLECTIONINFO.update(deps.storage, &address, 
         |collection_info|->StdResult<_>{
             let mut collection_info = collection_info.unwrap();
-            collection_info.start_mint_time = time;
+            if let Some(first) = collection_info.phases.first() {
+                let start = first.start_time;
+                for phase in collection_info.phases.iter_mut() {
+                    phase.end_time = phase.end_time - start + time;
+                    phase.start_time = phase.start_time - start + time;
+                }
+            }
             Ok(collection_info)
         })?;
     Ok(Response::default())
@@ -361,14 +605,7 @@ fn execute_free_minter(
     address: String,
     minters:Vec<String>
 ) -> Result<Response, ContractError> {
-   let state =CONFIG.load(deps.storage)?;
-    if state.owner != info.sender.to_string() {
-        return Err(ContractError::Unauthorized {});
-    }
-    let collection_info = COLLECTIONINFO.may_load(deps.storage, &address)?;
-    if collection_info == None{
-        return Err(ContractError::CollectionNotFound {  })
-    }
+    load_managed_collection(deps.storage, &info.sender, &address)?;
     for minter  in minters {
         let flag  = true;
         FREEMINTER.save(deps.storage, (&address,&minter),&flag)?;
@@ -377,426 +614,3114 @@ fn execute_free_minter(
 }
 
 
-fn execute_switch_type(
+fn execute_add_white_user(
     deps: DepsMut,
     info: MessageInfo,
     address: String,
-    public_mint:bool,
-    private_mint:bool,
-    free_mint:bool
+    white_users:Vec<WhiteUserInfo>
 ) -> Result<Response, ContractError> {
-   let state =CONFIG.load(deps.storage)?;
-    if state.owner != info.sender.to_string() {
-        return Err(ContractError::Unauthorized {});
-    }
-    let collection_info = COLLECTIONINFO.may_load(deps.storage, &address)?;
-    if collection_info == None{
-        return Err(ContractError::CollectionNotFound {  })
+    load_managed_collection(deps.storage, &info.sender, &address)?;
+    
+    for white_user in white_users{
+        WHITEUSERS.save(deps.storage, (&address,&white_user.address), &white_user.count)?;
     }
-    COLLECTIONINFO.update(deps.storage, &address, 
-        |collection_info| -> StdResult<_>{
-            let mut collection_info = collection_info.unwrap();
-                collection_info.public_mint = public_mint;
-                collection_info.private_mint = private_mint;
-                collection_info.free_mint = free_mint;
-            Ok(collection_info)
-        })?;
+
     Ok(Response::default())
 }
 
-
-fn execute_add_white_user(
+fn execute_remove_white_user(
     deps: DepsMut,
     info: MessageInfo,
     address: String,
-    white_users:Vec<WhiteUserInfo>
+    white_users:Vec<String>
 ) -> Result<Response, ContractError> {
-   let state =CONFIG.load(deps.storage)?;
-    if state.owner != info.sender.to_string() {
-        return Err(ContractError::Unauthorized {});
-    }
-    let collection_info = COLLECTIONINFO.may_load(deps.storage, &address)?;
-    if collection_info == None{
-        return Err(ContractError::CollectionNotFound {  })
-    }
-    
+    load_managed_collection(deps.storage, &info.sender, &address)?;
+
     for white_user in white_users{
-        WHITEUSERS.save(deps.storage, (&address,&white_user.address), &white_user.count)?;
+        WHITEUSERS.remove(deps.storage, (&address,&white_user));
     }
 
     Ok(Response::default())
 }
 
+fn execute_remove_free_minter(
+    deps: DepsMut,
+    info: MessageInfo,
+    address: String,
+    minters:Vec<String>
+) -> Result<Response, ContractError> {
+    load_managed_collection(deps.storage, &info.sender, &address)?;
 
+    for minter in minters{
+        FREEMINTER.remove(deps.storage, (&address,&minter));
+    }
 
+    Ok(Response::default())
+}
 
 
-
-fn execute_add_collection(
+fn execute_set_merkle_root(
     deps: DepsMut,
     info: MessageInfo,
-    members: Vec<AdminInfo>,
-    nft_address:String,
-    collection:CollectionInfo
-)->Result<Response,ContractError>{
-
-    let state = CONFIG.load(deps.storage)?;
+    address: String,
+    phase: String,
+    root: Option<String>
+) -> Result<Response, ContractError> {
+    let mut collection_info = load_managed_collection(deps.storage, &info.sender, &address)?;
 
-    deps.api.addr_validate(&nft_address)?;
+    if let Some(root) = &root {
+        if !is_hex_digest(root){
+            return Err(ContractError::InvalidMerkleRoot {  })
+        }
+    }
 
-    if info.sender.to_string() != state.owner{
-        return Err(ContractError::Unauthorized {});
+    let sale_phase = collection_info.phases.iter_mut().find(|sale_phase| sale_phase.name == phase);
+    match sale_phase {
+        Some(sale_phase) => sale_phase.merkle_root = root,
+        None => return Err(ContractError::PhaseNotFound {  })
     }
+    COLLECTIONINFO.save(deps.storage, &address, &collection_info)?;
 
-    let total_count =Uint128::u128(&collection.total_nft) as u32;
-    let check_length =  collection.check_mint.len() as u32;
+    Ok(Response::default())
+}
 
-    if total_count != check_length{
-        return Err(ContractError::WrongNumber {  })
+/// Mints from the reserved allocation, free of charge and outside the sale phases.
+/// Open to the owner, the collection creator and the collection admins.
+fn execute_mint_reserved(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    nft_address: String,
+    recipient: String,
+    quantity: u32
+) -> Result<Response, ContractError> {
+    deps.api.addr_validate(&recipient)?;
+    let collection_info = COLLECTIONINFO.may_load(deps.storage, &nft_address)?;
+    if collection_info.is_none(){
+        return Err(ContractError::CollectionNotFound {  })
     }
+    let mut collection_info = collection_info.unwrap();
 
-    
-    let mut total = Decimal::zero();
-    for admin in members.clone(){
-        deps.api.addr_validate(&admin.address)?;
-        total = total+admin.portion;
+    let state =CONFIG.load(deps.storage)?;
+    let admins = ADMININFO.load(deps.storage, &nft_address)?;
+    if state.owner != info.sender && collection_info.creator != info.sender
+        && !admins.iter().any(|admin| admin.address == info.sender) {
+        return Err(ContractError::Unauthorized {});
     }
 
-    if total!= Decimal::one(){
-        return Err(ContractError::WrongPortion {  })
+    if !collection_info.can_mint {
+        return Err(ContractError::CollectionNotApproved {  });
+    }
+    if quantity == 0 {
+        return Err(ContractError::ZeorError {  });
+    }
+    let mint_quantity = Uint128::from(quantity);
+    if collection_info.reserved_count + mint_quantity > collection_info.reserved {
+        return Err(ContractError::MintExceeded {  });
     }
 
-    ADMININFO.save(deps.storage,&nft_address,&members)?;
+    let messages = mint_tokens(deps.storage, &info, &env, &nft_address, &mut collection_info, &recipient, quantity)?;
+    collection_info.reserved_count += mint_quantity;
+    COLLECTIONINFO.save(deps.storage, &nft_address, &collection_info)?;
 
-    COLLECTIONINFO.save(deps.storage,&nft_address,&CollectionInfo{
-       total_nft:collection.total_nft,
-       mint_count:Uint128::new(0),
-       url:collection.url,
-       check_mint:collection.check_mint,
-       image_url:collection.image_url,
-       price:collection.price,
-       denom:collection.denom,
-       max_nft:collection.max_nft,
-       name:collection.name,
-       can_mint:true,
-       public_mint:collection.public_mint,
-       private_mint:collection.private_mint,
-       free_mint:collection.free_mint,
-       private_price:collection.private_price,
-       public_price:collection.public_price,
-       start_mint_time:collection.start_mint_time,
-       private_mint_period:collection.private_mint_period,
-       public_mint_period:collection.public_mint_period
-    })?;
-    Ok(Response::default())
+    Ok(Response::new()
+        .add_attribute("action", "mint_reserved")
+        .add_attribute("recipient", recipient)
+        .add_messages(messages))
 }
 
-fn execute_update_collection(
+/// Queues `recipients` behind any airdrop still pending for the collection, then mints up to
+/// `AIRDROP_BATCH` tokens from the head of the queue. Calling again with no recipients
+/// resumes a queue that did not fit in one transaction, touching only the entries it mints.
+/// Airdropped tokens come out of the sale supply, which stops selling what the queue still
+/// needs. Like registration this stays with the contract owner rather than the creator.
+fn execute_airdrop(
     deps: DepsMut,
+    env: Env,
     info: MessageInfo,
-    members: Vec<AdminInfo>,
-    nft_address:String,
-    collection:CollectionInfo
-)->Result<Response,ContractError>{
-
-    let state = CONFIG.load(deps.storage)?;
-
-    deps.api.addr_validate(&nft_address)?;
-
-    if info.sender.to_string() != state.owner{
+    nft_address: String,
+    recipients: Vec<(String,u32)>
+) -> Result<Response, ContractError> {
+    let state =CONFIG.load(deps.storage)?;
+    if state.owner != info.sender {
         return Err(ContractError::Unauthorized {});
     }
-
     let collection_info = COLLECTIONINFO.may_load(deps.storage, &nft_address)?;
-    if collection_info == None{
+    if collection_info.is_none(){
         return Err(ContractError::CollectionNotFound {  })
     }
-    let collection_info = collection_info.unwrap();
-    
-    let mut total = Decimal::zero();
-    for admin in members.clone(){
-        deps.api.addr_validate(&admin.address)?;
-        total = total+admin.portion;
+    let mut collection_info = collection_info.unwrap();
+    if !collection_info.can_mint {
+        return Err(ContractError::CollectionNotApproved {  });
     }
 
-    if total!= Decimal::one(){
-        return Err(ContractError::WrongPortion {  })
+    let mut queue = AIRDROPQUEUE.may_load(deps.storage, &nft_address)?.unwrap_or(AirdropQueue{
+        head:0,
+        tail:0,
+        pending:Uint128::zero()
+    });
+    for (recipient, quantity) in recipients.iter() {
+        deps.api.addr_validate(recipient)?;
+        if *quantity == 0 {
+            return Err(ContractError::ZeorError {  });
+        }
+        queue.pending += Uint128::from(*quantity);
+    }
+    let sale_count = collection_info.mint_count - collection_info.reserved_count;
+    if sale_count + queue.pending > collection_info.total_nft - collection_info.reserved {
+        return Err(ContractError::MintExceeded {  });
+    }
+    for recipient in recipients {
+        AIRDROPS.save(deps.storage, (&nft_address, queue.tail), &recipient)?;
+        queue.tail += 1;
     }
 
+    let mut messages = vec![];
+    let mut budget = AIRDROP_BATCH;
+    while budget > 0 && queue.head < queue.tail {
+        let (recipient, remaining) = AIRDROPS.load(deps.storage, (&nft_address, queue.head))?;
+        let quantity = remaining.min(budget);
+        messages.extend(mint_tokens(deps.storage, &info, &env, &nft_address, &mut collection_info, &recipient, quantity)?);
+        budget -= quantity;
+        queue.pending -= Uint128::from(quantity);
+        if quantity == remaining {
+            AIRDROPS.remove(deps.storage, (&nft_address, queue.head));
+            queue.head += 1;
+        } else {
+            AIRDROPS.save(deps.storage, (&nft_address, queue.head), &(recipient, remaining - quantity))?;
+        }
+    }
 
-    ADMININFO.save(deps.storage,&nft_address,&members)?;
+    if queue.head == queue.tail {
+        AIRDROPQUEUE.remove(deps.storage, &nft_address);
+    } else {
+        AIRDROPQUEUE.save(deps.storage, &nft_address, &queue)?;
+    }
+    COLLECTIONINFO.save(deps.storage, &nft_address, &collection_info)?;
 
-    COLLECTIONINFO.save(deps.storage,&nft_address,&CollectionInfo{
-       total_nft:collection.total_nft,
-       mint_count:collection_info.mint_count,
-       url:collection.url,
-       check_mint:collection_info.check_mint,
-       image_url:collection.image_url,
-       price:collection.price,
-       denom:collection.denom,
+    Ok(Response::new()
+        .add_attribute("action", "airdrop")
+        .add_attribute("minted", (AIRDROP_BATCH - budget).to_string())
+        .add_attribute("remaining", queue.pending.to_string())
+        .add_messages(messages))
+}
+
+/// Tokens queued by `Airdrop` and not minted yet.
+fn airdrop_pending(storage:&dyn Storage, address:&str) -> StdResult<Uint128> {
+    Ok(AIRDROPQUEUE.may_load(storage, address)?.map(|queue| queue.pending).unwrap_or_default())
+}
+
+/// Stores attribute sets by metadata number, up to `MAX_TRAIT_BATCH` tokens per call.
+/// Uploads close with the first mint so that buyers know what they draw from.
+fn execute_upload_traits(
+    deps: DepsMut,
+    info: MessageInfo,
+    nft_address: String,
+    traits: Vec<(u32,Vec<Trait>)>
+) -> Result<Response, ContractError> {
+    let collection_info = load_managed_collection(deps.storage, &info.sender, &nft_address)?;
+    if !collection_info.mint_count.is_zero() {
+        return Err(ContractError::MetadataLocked {  })
+    }
+    if traits.len() > MAX_TRAIT_BATCH {
+        return Err(ContractError::WrongNumber {  })
+    }
+
+    // traits are keyed by the numbers the collection mints: 1..=total_nft, with the entries of
+    // an explicit id list taking the place of the numbers they displace
+    let mut displaced = HashSet::new();
+    let mut listed = HashSet::new();
+    for entry in TOKENPOOL.prefix(&nft_address).range(deps.storage, None, None, Order::Ascending) {
+        let (index, token) = entry?;
+        displaced.insert(index + 1);
+        listed.insert(token);
+    }
+    let total = collection_info.total_nft.u128() as u32;
+
+    for (token_number, attributes) in traits {
+        let in_range = token_number >= 1 && token_number <= total && !displaced.contains(&token_number);
+        if !in_range && !listed.contains(&token_number) {
+            return Err(ContractError::WrongNumber {  })
+        }
+        TOKENTRAITS.save(deps.storage, (&nft_address, token_number), &attributes)?;
+    }
+    Ok(Response::new()
+        .add_attribute("action", "upload_traits"))
+}
+
+fn execute_reveal(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    address: String
+) -> Result<Response, ContractError> {
+    let collection_info = load_managed_collection(deps.storage, &info.sender, &address)?;
+
+    if collection_info.placeholder_url.is_none() || collection_info.provenance_hash.is_none(){
+        return Err(ContractError::NoProvenance {  })
+    }
+    if collection_info.reveal_offset.is_some(){
+        return Err(ContractError::AlreadyRevealed {  })
+    }
+
+    let (status, _, _) = mint_phase(&collection_info, env.block.time.seconds());
+    if collection_info.mint_count < collection_info.total_nft && status != MintStatus::Ended {
+        return Err(ContractError::RevealNotReady {  })
+    }
+    // reserved and airdropped tokens are drawn from the same pool, so they go out while it is hidden
+    if collection_info.reserved_count < collection_info.reserved || !airdrop_pending(deps.storage, &address)?.is_zero() {
+        return Err(ContractError::RevealNotReady {  })
+    }
+
+    let prng_seed = ENTROPY.may_load(deps.storage, &address)?.unwrap_or_default();
+    let random_seed = new_entropy(&info,&env, prng_seed.as_ref(), address.as_bytes());
+    let mut rng = ChaChaRng::from_seed(random_seed);
+    let offset = random_below(&mut rng, collection_info.total_nft.u128() as u32);
+
+    COLLECTIONINFO.update(deps.storage, &address, 
+        |collection_info|->StdResult<_>{
+            let mut collection_info = collection_info.unwrap();
+            collection_info.reveal_offset = Some(offset);
+            Ok(collection_info)
+        })?;
+    Ok(Response::new()
+        .add_attribute("action", "reveal")
+        .add_attribute("offset", offset.to_string()))
+}
+
+/// Provenance hashes are stored as lowercase hex. Delayed reveal maps token numbers onto
+/// metadata by offset, so it needs the collection numbered `1..=total_nft` and a
+/// provenance hash committed up front.
+fn check_reveal_settings(storage:&dyn Storage, address:&str, collection:&CollectionInfo) -> Result<(), ContractError> {
+    if let Some(provenance_hash) = &collection.provenance_hash {
+        if !is_hex_digest(provenance_hash){
+            return Err(ContractError::InvalidProvenance {  })
+        }
+    }
+    if collection.placeholder_url.is_none(){
+        return Ok(());
+    }
+    if collection.provenance_hash.is_none(){
+        return Err(ContractError::NoProvenance {  })
+    }
+    if !pool_is_range(storage, address)?{
+        return Err(ContractError::WrongNumber {  })
+    }
+    Ok(())
+}
+
+fn check_royalty(deps:Deps, collection:&CollectionInfo) -> Result<(), ContractError> {
+    if let Some(royalty) = &collection.royalty {
+        if royalty.percentage > 100 {
+            return Err(ContractError::InvalidRoyalty {  })
+        }
+        deps.api.addr_validate(&royalty.payment_address)?;
+    }
+    Ok(())
+}
+
+/// Phases need unique non-empty names and a non-empty time window, and must be listed
+/// in start order without overlapping so that at most one is active at a time.
+fn check_phases(phases:&[SalePhase]) -> Result<(), ContractError> {
+    let invalid = |reason:&str| Err(ContractError::InvalidPhases { reason: reason.to_string() });
+    for (index, phase) in phases.iter().enumerate() {
+        if phase.name.is_empty() {
+            return invalid("phase name is empty");
+        }
+        if phases[..index].iter().any(|other| other.name == phase.name) {
+            return invalid("phase names must be unique");
+        }
+        if phase.start_time >= phase.end_time {
+            return invalid("phase must end after it starts");
+        }
+        if index > 0 && phase.start_time < phases[index - 1].end_time {
+            return invalid("phases must be ordered and must not overlap");
+        }
+        if let Some(root) = &phase.merkle_root {
+            if !is_hex_digest(root){
+                return Err(ContractError::InvalidMerkleRoot {  })
+            }
+        }
+    }
+    Ok(())
+}
+
+fn execute_add_collection(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    members: Vec<AdminInfo>,
+    nft_address:String,
+    collection:CollectionInfo,
+    token_ids:Option<Vec<u32>>
+)->Result<Response,ContractError>{
+
+    let state = CONFIG.load(deps.storage)?;
+
+    deps.api.addr_validate(&nft_address)?;
+
+    if COLLECTIONINFO.has(deps.storage, &nft_address){
+        return Err(ContractError::CollectionExists {  })
+    }
+
+    check_cw721(deps.as_ref(), &env, &nft_address)?;
+
+    // anyone may register a cw721 contract they instantiated or administer, for the listing
+    // fee and subject to approval; the owner registers any collection for free
+    let registered_by_owner = info.sender == state.owner;
+    if !registered_by_owner {
+        let contract_info:ContractInfoResponse = deps.querier.query(&QueryRequest::Wasm(WasmQuery::ContractInfo {
+            contract_addr: nft_address.clone()
+        }))?;
+        if contract_info.creator != info.sender && contract_info.admin.as_ref() != Some(&info.sender.to_string()) {
+            return Err(ContractError::NotCollectionOwner {  })
+        }
+    }
+    let (creator, messages) = registration_terms(deps.as_ref(), &state, &info, &collection)?;
+
+    save_collection(deps, &nft_address, members, collection, token_ids, creator, registered_by_owner)?;
+    Ok(Response::new()
+        .add_attribute("action", "add_collection")
+        .add_attribute("approved", registered_by_owner.to_string())
+        .add_messages(messages))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn execute_instantiate_collection(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    members: Vec<AdminInfo>,
+    collection:CollectionInfo,
+    token_ids:Option<Vec<u32>>,
+    code_id:u64,
+    name:String,
+    symbol:String
+)->Result<Response,ContractError>{
+
+    let state = CONFIG.load(deps.storage)?;
+
+    // the new contract is ours, so only the fee and approval rules of AddCollection apply
+    let registered_by_owner = info.sender == state.owner;
+    let (creator, messages) = registration_terms(deps.as_ref(), &state, &info, &collection)?;
+
+    PENDINGCOLLECTION.save(deps.storage, &PendingCollection{
+        members,
+        collection:CollectionInfo{creator:creator.clone(), ..collection},
+        token_ids,
+        approved:registered_by_owner
+    })?;
+
+    let instantiate = WasmMsg::Instantiate {
+        admin: Some(creator),
+        code_id,
+        msg: to_binary(&Cw721InstantiateMsg{
+            name:name.clone(),
+            symbol,
+            minter:env.contract.address.to_string()
+        })?,
+        funds: vec![],
+        label: name
+    };
+
+    Ok(Response::new()
+        .add_attribute("action", "instantiate_collection")
+        .add_attribute("approved", registered_by_owner.to_string())
+        .add_messages(messages)
+        .add_submessage(SubMsg::reply_on_success(instantiate, INSTANTIATE_CW721_REPLY_ID)))
+}
+
+#[entry_point]
+pub fn reply(deps: DepsMut, _env: Env, msg: Reply) -> Result<Response, ContractError> {
+    if msg.id != INSTANTIATE_CW721_REPLY_ID {
+        return Err(ContractError::Std(StdError::generic_err(format!("Unknown reply id: {}", msg.id))));
+    }
+
+    let response = msg.result.into_result().map_err(StdError::generic_err)?;
+    let nft_address = response.events.iter()
+        .filter(|event| event.ty == "instantiate")
+        .flat_map(|event| event.attributes.iter())
+        .find(|attr| attr.key == "_contract_address")
+        .map(|attr| attr.value.clone())
+        .ok_or_else(|| StdError::generic_err("No contract address in instantiate reply"))?;
+
+    let pending = PENDINGCOLLECTION.load(deps.storage)?;
+    PENDINGCOLLECTION.remove(deps.storage);
+
+    if COLLECTIONINFO.has(deps.storage, &nft_address){
+        return Err(ContractError::CollectionExists {  })
+    }
+    deps.api.addr_validate(&nft_address)?;
+
+    let creator = pending.collection.creator.clone();
+    save_collection(deps, &nft_address, pending.members, pending.collection, pending.token_ids, creator, pending.approved)?;
+    Ok(Response::new()
+        .add_attribute("action", "add_collection")
+        .add_attribute("nft_address", nft_address)
+        .add_attribute("approved", pending.approved.to_string()))
+}
+
+/// A registered cw721 must take mints from this contract and must not have minted on its own.
+fn check_cw721(deps:Deps, env:&Env, nft_address:&str) -> Result<(), ContractError> {
+    let minter:MinterResponse = deps.querier.query_wasm_smart(nft_address, &Cw721QueryMsg::Minter {})?;
+    if minter.minter != env.contract.address {
+        return Err(ContractError::NotMinter {  })
+    }
+
+    let num_tokens:NumTokensResponse = deps.querier.query_wasm_smart(nft_address, &Cw721QueryMsg::NumTokens {})?;
+    if num_tokens.count != 0 {
+        return Err(ContractError::CollectionNotEmpty {  })
+    }
+    Ok(())
+}
+
+/// Resolves who is recorded as the collection creator and collects the listing fee from
+/// anyone but the owner.
+fn registration_terms(deps:Deps, state:&State, info:&MessageInfo, collection:&CollectionInfo) -> Result<(String, Vec<CosmosMsg>), ContractError> {
+    let mut messages:Vec<CosmosMsg> = vec![];
+    if info.sender == state.owner {
+        deps.api.addr_validate(&collection.creator)?;
+        return Ok((collection.creator.clone(), messages));
+    }
+
+    let fee = state.listing_fee.clone().unwrap_or(Coin{denom:String::new(),amount:Uint128::zero()});
+    let refunds = check_payment(&info.funds, &fee.denom, fee.amount)?;
+    if !fee.amount.is_zero() {
+        messages.push(CosmosMsg::Bank(BankMsg::Send {
+            to_address: state.treasury.clone(),
+            amount: vec![fee]
+        }));
+    }
+    if !refunds.is_empty() {
+        messages.push(CosmosMsg::Bank(BankMsg::Send {
+            to_address: info.sender.to_string(),
+            amount: refunds
+        }));
+    }
+    Ok((info.sender.to_string(), messages))
+}
+
+fn save_collection(
+    deps: DepsMut,
+    nft_address:&str,
+    members: Vec<AdminInfo>,
+    collection:CollectionInfo,
+    token_ids:Option<Vec<u32>>,
+    creator:String,
+    approved:bool
+)->Result<(),ContractError>{
+    let total_count =Uint128::u128(&collection.total_nft) as u32;
+
+    if total_count == 0 || Uint128::from(total_count) != collection.total_nft{
+        return Err(ContractError::WrongNumber {  })
+    }
+
+    // without an explicit list the collection is numbered 1..=total_nft
+    if let Some(token_ids) = token_ids {
+        if token_ids.len() as u32 != total_count{
+            return Err(ContractError::WrongNumber {  })
+        }
+        // every token is minted once, so the ids must be distinct and non-zero
+        let mut sorted = token_ids.clone();
+        sorted.sort_unstable();
+        sorted.dedup();
+        if sorted.len() != token_ids.len() || sorted.first() == Some(&0) {
+            return Err(ContractError::WrongNumber {  })
+        }
+        for (index, token_id) in token_ids.into_iter().enumerate() {
+            if token_id != index as u32 + 1 {
+                TOKENPOOL.save(deps.storage, (nft_address, index as u32), &token_id)?;
+            }
+        }
+    }
+
+    if collection.reserved > collection.total_nft {
+        return Err(ContractError::WrongNumber {  })
+    }
+
+    check_reveal_settings(deps.storage, nft_address, &collection)?;
+    check_phases(&collection.phases)?;
+    check_royalty(deps.as_ref(), &collection)?;
+    check_templates(&collection)?;
+
+    
+    let mut total = Decimal::zero();
+    for admin in members.clone(){
+        deps.api.addr_validate(&admin.address)?;
+        total = total+admin.portion;
+    }
+
+    if total!= Decimal::one(){
+        return Err(ContractError::WrongPortion {  })
+    }
+
+    ADMININFO.save(deps.storage,nft_address,&members)?;
+
+    COLLECTIONINFO.save(deps.storage,nft_address,&CollectionInfo{
+       total_nft:collection.total_nft,
+       mint_count:Uint128::new(0),
+       url:collection.url,
+       image_url:collection.image_url,
        max_nft:collection.max_nft,
        name:collection.name,
-       can_mint:true,
-       public_mint:collection.public_mint,
-       private_mint:collection.private_mint,
-       free_mint:collection.free_mint,
-       private_price:collection.private_price,
-       public_price:collection.public_price,
-       start_mint_time:collection.start_mint_time,
-       private_mint_period:collection.private_mint_period,
-       public_mint_period:collection.public_mint_period
+       can_mint:approved,
+       creator,
+       auto_payout:collection.auto_payout,
+       templates:collection.templates,
+       metadata:collection.metadata,
+       royalty:collection.royalty,
+       reserved:collection.reserved,
+       reserved_count:Uint128::zero(),
+       phases:collection.phases,
+       placeholder_url:collection.placeholder_url,
+       provenance_hash:collection.provenance_hash,
+       reveal_offset:None
     })?;
+    Ok(())
+}
+
+fn execute_set_listing_fee(
+    deps: DepsMut,
+    info: MessageInfo,
+    fee: Option<Coin>,
+) -> Result<Response, ContractError> {
+   let state =CONFIG.load(deps.storage)?;
+    if state.owner != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    CONFIG.update(deps.storage,
+        |mut state|-> StdResult<_>{
+            state.listing_fee = fee;
+            Ok(state)
+        }
+    )?;
+    Ok(Response::default())
+}
+
+fn execute_set_treasury(
+    deps: DepsMut,
+    info: MessageInfo,
+    address: String,
+) -> Result<Response, ContractError> {
+   let state =CONFIG.load(deps.storage)?;
+    if state.owner != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    deps.api.addr_validate(&address)?;
+
+    CONFIG.update(deps.storage,
+        |mut state|-> StdResult<_>{
+            state.treasury = address;
+            Ok(state)
+        }
+    )?;
+    Ok(Response::default())
+}
+
+/// Sends the sender everything that accrued to them from one collection, or from all of them.
+fn execute_withdraw(
+    deps: DepsMut,
+    info: MessageInfo,
+    nft_address: Option<String>
+) -> Result<Response, ContractError> {
+    let sender = info.sender.to_string();
+    let payouts = pending_payouts(deps.as_ref(), &sender, nft_address.as_deref())?;
+
+    let mut amount:Vec<Coin> = vec![];
+    for payout in payouts {
+        PAYOUTS.remove(deps.storage, (&sender, &payout.nft_address, &payout.denom));
+        OWEDPAYOUTS.update(deps.storage, &payout.denom, |owed| -> StdResult<_> {
+            Ok(owed.unwrap_or_default() - payout.amount)
+        })?;
+        if payout.amount.is_zero() {
+            continue;
+        }
+        match amount.iter_mut().find(|coin| coin.denom == payout.denom) {
+            Some(coin) => coin.amount += payout.amount,
+            None => amount.push(Coin{denom:payout.denom, amount:payout.amount})
+        }
+    }
+    // banks refuse zero coins, so a withdrawal only ever sends non-zero amounts
+    if amount.is_empty() {
+        return Err(ContractError::NothingToWithdraw {  });
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "withdraw")
+        .add_message(CosmosMsg::Bank(BankMsg::Send {
+            to_address: sender,
+            amount
+        })))
+}
+
+/// Sends the treasury whatever the contract holds beyond what admins can still withdraw.
+fn execute_sweep_dust(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo
+) -> Result<Response, ContractError> {
+   let state =CONFIG.load(deps.storage)?;
+    if state.owner != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let amount:Vec<Coin> = contract_balances(deps.as_ref(), &env)?
+        .into_iter()
+        .filter(|balance| !balance.dust.is_zero())
+        .map(|balance| Coin{denom:balance.denom, amount:balance.dust})
+        .collect();
+    if amount.is_empty() {
+        return Err(ContractError::NothingToWithdraw {  });
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "sweep_dust")
+        .add_message(CosmosMsg::Bank(BankMsg::Send {
+            to_address: state.treasury,
+            amount
+        })))
+}
+
+fn execute_set_platform_fee(
+    deps: DepsMut,
+    info: MessageInfo,
+    fee_bps: u64,
+) -> Result<Response, ContractError> {
+   let state =CONFIG.load(deps.storage)?;
+    if state.owner != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    if fee_bps > 10_000 {
+        return Err(ContractError::InvalidFee {});
+    }
+
+    CONFIG.update(deps.storage,
+        |mut state|-> StdResult<_>{
+            state.platform_fee_bps = fee_bps;
+            Ok(state)
+        }
+    )?;
+    Ok(Response::default())
+}
+
+/// Approves a self-registered collection for minting, or delists one so nothing more is minted.
+fn execute_set_listed(
+    deps: DepsMut,
+    info: MessageInfo,
+    nft_address: String,
+    listed: bool
+) -> Result<Response, ContractError> {
+   let state =CONFIG.load(deps.storage)?;
+    if state.owner != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    let collection_info = COLLECTIONINFO.may_load(deps.storage, &nft_address)?;
+    if collection_info.is_none(){
+        return Err(ContractError::CollectionNotFound {  })
+    }
+    COLLECTIONINFO.update(deps.storage, &nft_address, 
+        |collection_info|->StdResult<_>{
+            let mut collection_info = collection_info.unwrap();
+            collection_info.can_mint = listed;
+            Ok(collection_info)
+        })?;
     Ok(Response::default())
 }
 
+/// Drops a registration along with its settings, as long as nothing was minted from it.
+/// The listing fee is not refunded.
+fn execute_reject_collection(
+    deps: DepsMut,
+    info: MessageInfo,
+    nft_address: String
+) -> Result<Response, ContractError> {
+   let state =CONFIG.load(deps.storage)?;
+    if state.owner != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    let collection_info = COLLECTIONINFO.may_load(deps.storage, &nft_address)?;
+    if collection_info.is_none(){
+        return Err(ContractError::CollectionNotFound {  })
+    }
+    if !collection_info.unwrap().mint_count.is_zero() || AIRDROPQUEUE.has(deps.storage, &nft_address) {
+        return Err(ContractError::CollectionLive {  })
+    }
+
+    let pool:Vec<u32> = TOKENPOOL.prefix(&nft_address).keys(deps.storage, None, None, Order::Ascending).collect::<StdResult<_>>()?;
+    for index in pool {
+        TOKENPOOL.remove(deps.storage, (&nft_address, index));
+    }
+    let white_users:Vec<String> = WHITEUSERS.prefix(&nft_address).keys(deps.storage, None, None, Order::Ascending).collect::<StdResult<_>>()?;
+    for white_user in white_users {
+        WHITEUSERS.remove(deps.storage, (&nft_address, &white_user));
+    }
+    let minters:Vec<String> = FREEMINTER.prefix(&nft_address).keys(deps.storage, None, None, Order::Ascending).collect::<StdResult<_>>()?;
+    for minter in minters {
+        FREEMINTER.remove(deps.storage, (&nft_address, &minter));
+    }
+    let traits:Vec<u32> = TOKENTRAITS.prefix(&nft_address).keys(deps.storage, None, None, Order::Ascending).collect::<StdResult<_>>()?;
+    for token_number in traits {
+        TOKENTRAITS.remove(deps.storage, (&nft_address, token_number));
+    }
+    ADMININFO.remove(deps.storage, &nft_address);
+    COLLECTIONINFO.remove(deps.storage, &nft_address);
+
+    Ok(Response::new()
+        .add_attribute("action", "reject_collection"))
+}
+
+fn execute_update_collection(
+    deps: DepsMut,
+    info: MessageInfo,
+    members: Vec<AdminInfo>,
+    nft_address:String,
+    collection:CollectionInfo
+)->Result<Response,ContractError>{
+
+    let state = CONFIG.load(deps.storage)?;
+
+    deps.api.addr_validate(&nft_address)?;
+
+    let collection_info = load_managed_collection(deps.storage, &info.sender, &nft_address)?;
+
+    // only the owner hands a collection over to another creator
+    let creator = if state.owner == info.sender {
+        deps.api.addr_validate(&collection.creator)?;
+        collection.creator.clone()
+    } else {
+        collection_info.creator
+    };
+
+    if collection_info.mint_count > Uint128::zero(){
+        if collection.provenance_hash != collection_info.provenance_hash{
+            return Err(ContractError::ProvenanceLocked {  })
+        }
+        if collection.placeholder_url != collection_info.placeholder_url{
+            return Err(ContractError::RevealLocked {  })
+        }
+        // changing how ids are built could clash with tokens already minted
+        if collection.templates.as_ref().map(|templates| &templates.token_id) != collection_info.templates.as_ref().map(|templates| &templates.token_id) {
+            return Err(ContractError::MetadataLocked {  })
+        }
+    }
+    if collection.total_nft != collection_info.total_nft{
+        return Err(ContractError::WrongNumber {  })
+    }
+    if collection.reserved > collection.total_nft || collection.reserved < collection_info.reserved_count
+        || collection.total_nft - collection.reserved < collection_info.mint_count - collection_info.reserved_count + airdrop_pending(deps.storage, &nft_address)? {
+        return Err(ContractError::WrongNumber {  })
+    }
+    // reveal settings are frozen from the first mint on, when the pool no longer holds every token
+    if collection_info.mint_count.is_zero(){
+        check_reveal_settings(deps.storage, &nft_address, &collection)?;
+    }
+    check_phases(&collection.phases)?;
+    check_royalty(deps.as_ref(), &collection)?;
+    check_templates(&collection)?;
+    
+    let mut total = Decimal::zero();
+    for admin in members.clone(){
+        deps.api.addr_validate(&admin.address)?;
+        total = total+admin.portion;
+    }
+
+    if total!= Decimal::one(){
+        return Err(ContractError::WrongPortion {  })
+    }
+
+
+    ADMININFO.save(deps.storage,&nft_address,&members)?;
+
+    COLLECTIONINFO.save(deps.storage,&nft_address,&CollectionInfo{
+       total_nft:collection.total_nft,
+       mint_count:collection_info.mint_count,
+       url:collection.url,
+       image_url:collection.image_url,
+       max_nft:collection.max_nft,
+       name:collection.name,
+       can_mint:collection_info.can_mint,
+       creator,
+       auto_payout:collection.auto_payout,
+       templates:collection.templates,
+       metadata:collection.metadata,
+       royalty:collection.royalty,
+       reserved:collection.reserved,
+       reserved_count:collection_info.reserved_count,
+       phases:collection.phases,
+       placeholder_url:collection.placeholder_url,
+       provenance_hash:collection.provenance_hash,
+       reveal_offset:collection_info.reveal_offset
+    })?;
+    Ok(Response::default())
+}
+
+
+
+
+#[entry_point]
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::GetStateInfo {} => to_binary(& query_state_info(deps)?),
+        QueryMsg::GetAdminInfo { nft_address }=>to_binary(& query_admin_info(deps,nft_address)?),
+        QueryMsg::GetUserInfo {nft_address, address }=>to_binary(& query_user_info(deps,nft_address,address)?),
+        QueryMsg::GetCollectionInfo { nft_address,address }=>to_binary(& query_collection_info(deps,env,nft_address,address)?),
+        QueryMsg::GetTokenUri { nft_address, token_number }=>to_binary(& query_token_uri(deps,nft_address,token_number)?),
+        QueryMsg::GetProvenance { nft_address }=>to_binary(& query_provenance(deps,nft_address)?),
+        QueryMsg::ListCollections { start_after, limit }=>to_binary(& query_list_collections(deps,env,start_after,limit)?),
+        QueryMsg::GetWhiteUserInfo { nft_address, address }=>to_binary(& query_white_user_info(deps,nft_address,address)?),
+        QueryMsg::ListWhiteUsers { nft_address, start_after, limit }=>to_binary(& query_list_white_users(deps,nft_address,start_after,limit)?),
+        QueryMsg::ListFreeMinters { nft_address, start_after, limit }=>to_binary(& query_list_free_minters(deps,nft_address,start_after,limit)?),
+        QueryMsg::GetMerkleRoot { nft_address, phase }=>to_binary(& query_merkle_root(deps,nft_address,phase)?),
+        QueryMsg::GetPendingPayouts { address, nft_address }=>to_binary(& query_pending_payouts(deps,address,nft_address)?),
+        QueryMsg::GetBalances {}=>to_binary(& query_balances(deps,env)?),
+        QueryMsg::GetTokenTraits { nft_address, token_number }=>to_binary(& query_token_traits(deps,nft_address,token_number)?)
+    }
+}
+
+
+pub fn query_state_info(deps:Deps) -> StdResult<State>{
+    let state = CONFIG.load(deps.storage)?;
+    Ok(state)
+}
+
+pub fn query_admin_info(deps:Deps,nft_address:String) -> StdResult<Vec<AdminInfo>>{
+   let admin = ADMININFO.load(deps.storage,&nft_address)?;
+   Ok(admin)
+}
+
+pub fn query_user_info(deps:Deps, nft_address:String,address:String) -> StdResult<Uint128>{
+   let user_info = USERINFO.may_load(deps.storage, (&nft_address,&address))?;
+   Ok(user_info.unwrap_or_default())
+}
+
+pub fn query_collection_info(deps:Deps,env:Env,nft_address:String,address: String) -> StdResult<CollectionInfoResponse>{
+   let collection_info = COLLECTIONINFO.load(deps.storage,&nft_address)?;
+   let (status, phase, phase_remaining) = mint_phase(&collection_info, env.block.time.seconds());
+   let phase = phase.cloned();
+   let free_minter = FREEMINTER.may_load(deps.storage, (&nft_address,&address))?.unwrap_or(false);
+   let price = phase.as_ref().map_or(Uint128::zero(), |phase| mint_price(phase, free_minter));
+   Ok(CollectionInfoResponse{
+    info:collection_info,
+    status,
+    phase,
+    phase_remaining,
+    price
+   })
+}
+
+pub fn query_token_uri(deps:Deps,nft_address:String,token_number:u32) -> StdResult<TokenUriResponse>{
+   let collection_info = COLLECTIONINFO.load(deps.storage,&nft_address)?;
+   check_token_number(&collection_info, token_number)?;
+   Ok(token_uri(&collection_info, token_number))
+}
+
+/// Attributes a token resolves to, `None` while its collection is hidden or when none were
+/// uploaded. Like `GetTokenUri` it follows the reveal, while minted extensions do not.
+pub fn query_token_traits(deps:Deps,nft_address:String,token_number:u32) -> StdResult<Option<Vec<Trait>>>{
+   let collection_info = COLLECTIONINFO.load(deps.storage,&nft_address)?;
+   check_token_number(&collection_info, token_number)?;
+   match metadata_number(&collection_info, token_number) {
+    Some(number) => TOKENTRAITS.may_load(deps.storage, (&nft_address,number)),
+    None => Ok(None)
+   }
+}
+
+pub fn query_provenance(deps:Deps,nft_address:String) -> StdResult<ProvenanceResponse>{
+   let collection_info = COLLECTIONINFO.load(deps.storage,&nft_address)?;
+   Ok(ProvenanceResponse{
+    provenance_hash:collection_info.provenance_hash,
+    locked:collection_info.mint_count > Uint128::zero(),
+    reveal_offset:collection_info.reveal_offset
+   })
+}
+
+pub fn query_list_collections(deps:Deps,env:Env,start_after:Option<String>,limit:Option<u32>) -> StdResult<CollectionsResponse>{
+   let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+   let start = start_after.as_deref().map(Bound::exclusive);
+   let now = env.block.time.seconds();
+
+   let collections = COLLECTIONINFO
+    .range(deps.storage, start, None, Order::Ascending)
+    .take(limit)
+    .map(|item| {
+        let (nft_address, collection_info) = item?;
+        let (status, phase, _) = mint_phase(&collection_info, now);
+        Ok(CollectionSummary{
+            nft_address,
+            status,
+            phase:phase.map(|phase| phase.name.clone()),
+            price:phase.map_or(Uint128::zero(), |phase| mint_price(phase, false)),
+            denom:phase.map(|phase| phase.denom.clone()),
+            name:collection_info.name,
+            mint_count:collection_info.mint_count,
+            total_nft:collection_info.total_nft,
+            can_mint:collection_info.can_mint
+        })
+    })
+    .collect::<StdResult<Vec<_>>>()?;
+   Ok(CollectionsResponse{collections})
+}
+
+/// Remaining whitelist allocation of `address`, `None` when it is not whitelisted.
+pub fn query_white_user_info(deps:Deps,nft_address:String,address:String) -> StdResult<Option<WhiteUserInfo>>{
+   let count = WHITEUSERS.may_load(deps.storage, (&nft_address,&address))?;
+   Ok(count.map(|count| WhiteUserInfo{address, count}))
+}
+
+pub fn query_list_white_users(deps:Deps,nft_address:String,start_after:Option<String>,limit:Option<u32>) -> StdResult<WhiteUsersResponse>{
+   let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+   let start = start_after.as_deref().map(Bound::exclusive);
+
+   let white_users = WHITEUSERS
+    .prefix(&nft_address)
+    .range(deps.storage, start, None, Order::Ascending)
+    .take(limit)
+    .map(|item| item.map(|(address, count)| WhiteUserInfo{address, count}))
+    .collect::<StdResult<Vec<_>>>()?;
+   Ok(WhiteUsersResponse{white_users})
+}
+
+pub fn query_list_free_minters(deps:Deps,nft_address:String,start_after:Option<String>,limit:Option<u32>) -> StdResult<FreeMintersResponse>{
+   let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+   let start = start_after.as_deref().map(Bound::exclusive);
+
+   let minters = FREEMINTER
+    .prefix(&nft_address)
+    .keys(deps.storage, start, None, Order::Ascending)
+    .take(limit)
+    .collect::<StdResult<Vec<_>>>()?;
+   Ok(FreeMintersResponse{minters})
+}
+
+pub fn query_merkle_root(deps:Deps,nft_address:String,phase:String) -> StdResult<Option<String>>{
+   let collection_info = COLLECTIONINFO.load(deps.storage,&nft_address)?;
+   match collection_info.phases.into_iter().find(|sale_phase| sale_phase.name == phase) {
+    Some(sale_phase) => Ok(sale_phase.merkle_root),
+    None => Err(StdError::not_found("SalePhase"))
+   }
+}
+
+pub fn query_pending_payouts(deps:Deps,address:String,nft_address:Option<String>) -> StdResult<PendingPayoutsResponse>{
+   let payouts = pending_payouts(deps, &address, nft_address.as_deref())?;
+   Ok(PendingPayoutsResponse{payouts})
+}
+
+pub fn query_balances(deps:Deps,env:Env) -> StdResult<BalancesResponse>{
+   let balances = contract_balances(deps, &env)?;
+   Ok(BalancesResponse{balances})
+}
+
+fn contract_balances(deps:Deps, env:&Env) -> StdResult<Vec<BalanceInfo>> {
+    deps.querier.query_all_balances(env.contract.address.to_string())?
+        .into_iter()
+        .map(|coin| {
+            let owed = OWEDPAYOUTS.may_load(deps.storage, &coin.denom)?.unwrap_or_default();
+            Ok(BalanceInfo{
+                dust:coin.amount.saturating_sub(owed),
+                denom:coin.denom,
+                balance:coin.amount,
+                owed
+            })
+        })
+        .collect()
+}
+
+fn pending_payouts(deps:Deps, address:&str, nft_address:Option<&str>) -> StdResult<Vec<PendingPayout>> {
+    match nft_address {
+        Some(nft_address) => PAYOUTS
+            .prefix((address, nft_address))
+            .range(deps.storage, None, None, Order::Ascending)
+            .map(|item| item.map(|(denom, amount)| PendingPayout{nft_address:nft_address.to_string(), denom, amount}))
+            .collect(),
+        None => PAYOUTS
+            .sub_prefix(address)
+            .range(deps.storage, None, None, Order::Ascending)
+            .map(|item| item.map(|((nft_address, denom), amount)| PendingPayout{nft_address, denom, amount}))
+            .collect()
+    }
+}
+
+/// Finds the sale phase running at `now`. Before the first phase and in the gaps between
+/// phases the status is `NotStarted` with the next phase and the seconds until it opens;
+/// during a phase it is `Active` with the seconds until it closes. A revealed collection
+/// has `Ended`, since its token to metadata mapping is public from then on.
+pub fn mint_phase(collection_info:&CollectionInfo, now:u64) -> (MintStatus, Option<&SalePhase>, Option<u64>) {
+    if collection_info.reveal_offset.is_some() {
+        return (MintStatus::Ended, None, None);
+    }
+    match collection_info.phases.iter().find(|phase| now < phase.end_time) {
+        Some(phase) if now < phase.start_time => (MintStatus::NotStarted, Some(phase), Some(phase.start_time - now)),
+        Some(phase) => (MintStatus::Active, Some(phase), Some(phase.end_time - now)),
+        None if collection_info.phases.is_empty() => (MintStatus::NotStarted, None, None),
+        None => (MintStatus::Ended, None, None)
+    }
+}
+
+pub fn new_entropy(info:&MessageInfo,env: &Env, seed: &[u8], entropy: &[u8]) -> [u8; 32] {
+    // 8 bytes each for block height and block time, 4 for the transaction index.
+    let entropy_len = 20 + info.sender.to_string().len() + entropy.len();
+    let mut rng_entropy = Vec::with_capacity(entropy_len);
+    rng_entropy.extend_from_slice(&env.block.height.to_be_bytes());
+    rng_entropy.extend_from_slice(&env.block.time.nanos().to_be_bytes());
+    if let Some(transaction) = &env.transaction {
+        rng_entropy.extend_from_slice(&transaction.index.to_be_bytes());
+    }
+    rng_entropy.extend_from_slice(info.sender.as_bytes());
+    rng_entropy.extend_from_slice(entropy);
+
+    let mut rng = Prng::new(seed, &rng_entropy);
+
+    rng.rand_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{MetadataTemplate, Royalty, TokenTemplates};
+    use cosmwasm_std::testing::{mock_env, mock_info, MockApi, MockQuerier, MockStorage, MOCK_CONTRACT_ADDR};
+    use cosmwasm_std::{CosmosMsg, ContractResult, Empty, Event, OwnedDeps, Querier, QuerierResult, SubMsgExecutionResponse, SubMsgResult, SystemResult, from_binary, from_slice};
+    use std::marker::PhantomData;
+
+    fn env_at(seconds:u64) -> Env {
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(seconds);
+        env
+    }
+
+    #[test]
+    fn buy_token() {
+        let mut deps = mock_cw721_dependencies();
+        let instantiate_msg = InstantiateMsg {
+            owner:"creator".to_string(),
+        };
+        let info = mock_info("creator", &[]);
+        let res = instantiate(deps.as_mut(), mock_env(), info, instantiate_msg).unwrap();
+        assert_eq!(0, res.messages.len());
+        
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::AddCollection { members: vec![AdminInfo{
+            address:"admin1".to_string(),
+            portion:Decimal::from_ratio(70u128, 100u128)
+        },
+        AdminInfo{
+            address:"admin2".to_string(),
+             portion:Decimal::from_ratio(30u128, 100u128)
+        }], 
+        nft_address: "collection1".to_string(),
+        collection: CollectionInfo { 
+            total_nft:Uint128::new(10),
+            url :"url".to_string(),
+            image_url:"imag_url".to_string(),
+            max_nft:Uint128::new(1),
+            mint_count:Uint128::new(0),
+            name:"Collection1".to_string(),
+            can_mint:true,
+            creator:"artist".to_string(),
+            auto_payout:true,
+            templates:None,
+            metadata:None,
+            royalty:None,
+            reserved:Uint128::zero(),
+            reserved_count:Uint128::zero(),
+            phases:mock_phases(mock_env().block.time.seconds()-10),
+            placeholder_url:None,
+            provenance_hash:None,
+            reveal_offset:None
+            },
+        token_ids: None
+        };
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::SetMintFlag { address: "collection1".to_string(), time: mock_env().block.time.seconds() };
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let collection_info = query_collection_info(deps.as_ref(), env_at(60), "collection1".to_string(),"user".to_string()).unwrap();
+        assert_eq!(collection_info.price,Uint128::new(20));
+        assert_eq!(collection_info.status,MintStatus::Active);
+        assert_eq!(collection_info.phase.unwrap().name,"public");
+        assert_eq!(collection_info.phase_remaining,Some(40));
+       
+        let user_info = query_user_info(deps.as_ref(), "collection1".to_string(), "user".to_string()).unwrap();
+        assert_eq!(user_info,Uint128::new(0));
+
+
+        let info = mock_info("minter1", &[Coin{
+            denom:"ujunox".to_string(),
+            amount:Uint128::new(20)
+        }]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: None, proof: None };
+        let res = execute(deps.as_mut(), env_at(60), info, msg).unwrap();
+        let collection_info = query_collection_info(deps.as_ref(), env_at(60), "collection1".to_string(),"user".to_string()).unwrap();
+        assert_eq!(collection_info.price,Uint128::new(20));
+        
+        assert_eq!(res.messages[1].msg,CosmosMsg::Bank(BankMsg::Send {
+                to_address: "admin1".to_string(),
+                amount:vec![Coin{
+                    denom:"ujunox".to_string(),
+                    amount:Uint128::new(14)
+                }]
+        }));
+       
+        assert_eq!(res.messages[2].msg,CosmosMsg::Bank(BankMsg::Send {
+                to_address: "admin2".to_string(),
+                amount:vec![Coin{
+                    denom:"ujunox".to_string(),
+                    amount:Uint128::new(6)
+                }]
+        }));
+
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::AddFreeMinter { address: "collection1".to_string(), minters: vec!["minter1".to_string()] };
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+
+        let info = mock_info("minter1", &[]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: None, proof: None };
+        let res = execute(deps.as_mut(), env_at(60), info, msg).unwrap();
+        assert_eq!(res.messages.len(),1);
+
+
+       let collection_info = query_collection_info(deps.as_ref(), mock_env(), "collection1".to_string(),"user".to_string()).unwrap();
+       assert_eq!(collection_info.price,Uint128::new(10));
+       assert_eq!(collection_info.phase.unwrap().name,"private");
+       assert_eq!(collection_info.phase_remaining,Some(50));
+
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::AddWhiteUsers { address: "collection1".to_string(), white_users: vec![WhiteUserInfo{
+            address:"minter2".to_string(),
+            count:Uint128::new(1)
+        }] };
+       execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+
+
+        let info = mock_info("minter2", &[Coin{
+            denom:"ujunox".to_string(),
+            amount:Uint128::new(10)
+        }]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: None, proof: None };
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+         
+        assert_eq!(res.messages[1].msg,CosmosMsg::Bank(BankMsg::Send {
+                to_address: "admin1".to_string(),
+                amount:vec![Coin{
+                    denom:"ujunox".to_string(),
+                    amount:Uint128::new(7)
+                }]
+        }));
+       
+        assert_eq!(res.messages[2].msg,CosmosMsg::Bank(BankMsg::Send {
+                to_address: "admin2".to_string(),
+                amount:vec![Coin{
+                    denom:"ujunox".to_string(),
+                    amount:Uint128::new(3)
+                }]
+        }));
+
+       
+
+         let collection_info = query_collection_info(deps.as_ref(), env_at(110), "collection1".to_string(),"user".to_string()).unwrap();
+        assert_eq!(collection_info.price,Uint128::new(0));
+        assert_eq!(collection_info.phase.unwrap().name,"free");
+
+        let info = mock_info("minter3", &[]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: None, proof: None };
+        let res = execute(deps.as_mut(), env_at(110), info, msg).unwrap();
+        assert_eq!(res.messages.len(),1);
+
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::AddFreeMinter { address: "collection1".to_string(), minters: vec!["minter3".to_string()] };
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+
+        let info = mock_info("minter3", &[]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: None, proof: None };
+        let res = execute(deps.as_mut(), env_at(110), info, msg).unwrap();
+        assert_eq!(res.messages.len(),1);
+
+        let collection_info = query_collection_info(deps.as_ref(), mock_env(), "collection1".to_string(),"user".to_string()).unwrap();
+        assert_eq!(collection_info.info.mint_count,Uint128::new(5));
+        
+
+    }
+
+    fn mock_phase(name:&str, start_time:u64, end_time:u64, price:u128) -> SalePhase {
+        SalePhase {
+            name:name.to_string(),
+            start_time,
+            end_time,
+            price:Uint128::new(price),
+            denom:"ujunox".to_string(),
+            max_per_wallet:None,
+            whitelist:false,
+            merkle_root:None,
+            max_supply:None
+        }
+    }
+
+    /// Whitelisted private sale, then a public sale, then a free mint, 50 seconds each.
+    fn mock_phases(start:u64) -> Vec<SalePhase> {
+        vec![
+            SalePhase{
+                whitelist:true,
+                ..mock_phase("private", start, start + 50, 10)
+            },
+            mock_phase("public", start + 50, start + 100, 20),
+            mock_phase("free", start + 100, start + 150, 0)
+        ]
+    }
+
+    fn mock_collection(start_mint_time:u64, max_nft:u128) -> CollectionInfo {
+        CollectionInfo {
+            total_nft:Uint128::new(10),
+            url :"url".to_string(),
+            image_url:"imag_url".to_string(),
+            max_nft:Uint128::new(max_nft),
+            mint_count:Uint128::new(0),
+            name:"Collection1".to_string(),
+            can_mint:true,
+            creator:"artist".to_string(),
+            auto_payout:true,
+            templates:None,
+            metadata:None,
+            royalty:None,
+            reserved:Uint128::zero(),
+            reserved_count:Uint128::zero(),
+            phases:mock_phases(start_mint_time),
+            placeholder_url:None,
+            provenance_hash:None,
+            reveal_offset:None
+        }
+    }
+
+    fn setup_collection(mut deps:DepsMut, collection:CollectionInfo) {
+        let info = mock_info("creator", &[]);
+        instantiate(deps.branch(), mock_env(), info, InstantiateMsg{owner:"creator".to_string()}).unwrap();
+
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::AddCollection { members: vec![AdminInfo{
+            address:"admin1".to_string(),
+            portion:Decimal::from_ratio(70u128, 100u128)
+        },
+        AdminInfo{
+            address:"admin2".to_string(),
+            portion:Decimal::from_ratio(30u128, 100u128)
+        }],
+        nft_address: "collection1".to_string(),
+        collection,
+        token_ids: None
+        };
+        execute(deps, mock_env(), info, msg).unwrap();
+    }
+
+    #[test]
+    fn sale_phase_follows_block_time() {
+        let mut deps = mock_cw721_dependencies();
+        setup_collection(deps.as_mut(), mock_collection(mock_env().block.time.seconds()+100, 5));
+
+        let collection_info = query_collection_info(deps.as_ref(), mock_env(), "collection1".to_string(),"user".to_string()).unwrap();
+        assert_eq!(collection_info.status,MintStatus::NotStarted);
+        assert_eq!(collection_info.phase.unwrap().name,"private");
+        assert_eq!(collection_info.phase_remaining,Some(100));
+
+        let info = mock_info("minter1", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(20)}]);
+        let err = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Mint { address: "collection1".to_string(), quantity: None, proof: None }).unwrap_err();
+        assert!(matches!(err, ContractError::MintNotStarted {}));
+
+        // private window needs a whitelist entry
+        let info = mock_info("minter1", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(10)}]);
+        let err = execute(deps.as_mut(), env_at(120), info, ExecuteMsg::Mint { address: "collection1".to_string(), quantity: None, proof: None }).unwrap_err();
+        assert!(matches!(err, ContractError::NotWhiteUsers {}));
+
+        // public window opens on its own
+        let info = mock_info("minter1", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(20)}]);
+        execute(deps.as_mut(), env_at(150), info, ExecuteMsg::Mint { address: "collection1".to_string(), quantity: None, proof: None }).unwrap();
+
+        let collection_info = query_collection_info(deps.as_ref(), env_at(199), "collection1".to_string(),"user".to_string()).unwrap();
+        assert_eq!(collection_info.phase.unwrap().name,"public");
+        assert_eq!(collection_info.phase_remaining,Some(1));
+
+        let collection_info = query_collection_info(deps.as_ref(), env_at(250), "collection1".to_string(),"user".to_string()).unwrap();
+        assert_eq!(collection_info.status,MintStatus::Ended);
+        assert_eq!(collection_info.phase,None);
+        assert_eq!(collection_info.phase_remaining,None);
+
+        let info = mock_info("minter1", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(20)}]);
+        let err = execute(deps.as_mut(), env_at(250), info, ExecuteMsg::Mint { address: "collection1".to_string(), quantity: None, proof: None }).unwrap_err();
+        assert!(matches!(err, ContractError::MintEnded {}));
+    }
+
+    #[test]
+    fn sale_phases() {
+        let mut deps = mock_cw721_dependencies();
+        let now = mock_env().block.time.seconds();
+
+        let info = mock_info("creator", &[]);
+        instantiate(deps.as_mut(), mock_env(), info, InstantiateMsg{owner:"creator".to_string()}).unwrap();
+
+        for phases in [
+            vec![mock_phase("early", now, now + 50, 10), mock_phase("early", now + 50, now + 100, 20)],
+            vec![mock_phase("early", now, now + 50, 10), mock_phase("late", now + 40, now + 100, 20)],
+            vec![mock_phase("early", now + 50, now + 50, 10)]
+        ] {
+            let info = mock_info("creator", &[]);
+            let msg = ExecuteMsg::AddCollection { members: vec![AdminInfo{
+                address:"admin1".to_string(),
+                portion:Decimal::one()
+            }],
+            nft_address: "collection1".to_string(),
+            collection: CollectionInfo{phases, ..mock_collection(now, 10)},
+            token_ids: None};
+            let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+            assert!(matches!(err, ContractError::InvalidPhases { .. }));
+        }
+
+        // an early bird phase capped at 3 tokens, then a gap, then a uatom sale of 2 per wallet
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::AddCollection { members: vec![AdminInfo{
+            address:"admin1".to_string(),
+            portion:Decimal::one()
+        }],
+        nft_address: "collection1".to_string(),
+        collection: CollectionInfo{
+            phases:vec![
+                SalePhase{max_supply:Some(Uint128::new(3)), ..mock_phase("early", now, now + 50, 5)},
+                SalePhase{
+                    denom:"uatom".to_string(),
+                    max_per_wallet:Some(Uint128::new(2)),
+                    ..mock_phase("late", now + 100, now + 200, 20)
+                }
+            ],
+            ..mock_collection(now, 10)
+        },
+        token_ids: None};
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let info = mock_info("minter1", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(10)}]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: Some(2), proof: None };
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!(res.attributes[0].value,"early");
+
+        let info = mock_info("minter2", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(10)}]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: Some(2), proof: None };
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::PhaseSoldOut { .. }));
+
+        let collection_info = query_collection_info(deps.as_ref(), env_at(60), "collection1".to_string(),"user".to_string()).unwrap();
+        assert_eq!(collection_info.status,MintStatus::NotStarted);
+        assert_eq!(collection_info.phase.unwrap().name,"late");
+        assert_eq!(collection_info.phase_remaining,Some(40));
+
+        let info = mock_info("minter1", &[Coin{denom:"uatom".to_string(),amount:Uint128::new(20)}]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: None, proof: None };
+        let err = execute(deps.as_mut(), env_at(60), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::MintNotStarted {}));
+
+        // the late phase limits wallets on its own, whatever they minted earlier
+        let info = mock_info("minter1", &[Coin{denom:"uatom".to_string(),amount:Uint128::new(60)}]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: Some(3), proof: None };
+        let err = execute(deps.as_mut(), env_at(150), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::MintExceeded {}));
+
+        let info = mock_info("minter1", &[Coin{denom:"uatom".to_string(),amount:Uint128::new(40)}]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: Some(2), proof: None };
+        let res = execute(deps.as_mut(), env_at(150), info, msg).unwrap();
+        assert_eq!(res.messages[2].msg,CosmosMsg::Bank(BankMsg::Send {
+                to_address: "admin1".to_string(),
+                amount:vec![Coin{denom:"uatom".to_string(),amount:Uint128::new(40)}]
+        }));
+        assert_eq!(query_user_info(deps.as_ref(), "collection1".to_string(), "minter1".to_string()).unwrap(),Uint128::new(4));
+
+        // moving the schedule keeps the phase lengths and the gap
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::SetMintFlag { address: "collection1".to_string(), time: now + 1000 };
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        let collection_info = query_collection_info(deps.as_ref(), env_at(150), "collection1".to_string(),"user".to_string()).unwrap();
+        assert_eq!(collection_info.info.phases[0].start_time,now + 1000);
+        assert_eq!(collection_info.info.phases[1].start_time,now + 1100);
+        assert_eq!(collection_info.info.phases[1].end_time,now + 1200);
+        assert_eq!(collection_info.status,MintStatus::NotStarted);
+
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::SetMerkleRoot { address: "collection1".to_string(), phase: "public".to_string(), root: None };
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::PhaseNotFound {}));
+    }
+
+    #[test]
+    fn reserved_allocation() {
+        let mut deps = mock_cw721_dependencies();
+        let now = mock_env().block.time.seconds();
+        setup_collection(deps.as_mut(), CollectionInfo{
+            reserved:Uint128::new(4),
+            ..mock_collection(now, 10)
+        });
+
+        // the sale stops at total_nft - reserved
+        let info = mock_info("minter1", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(140)}]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: Some(7), proof: None };
+        let err = execute(deps.as_mut(), env_at(60), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::MintExceeded {}));
+
+        let info = mock_info("minter1", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(120)}]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: Some(6), proof: None };
+        execute(deps.as_mut(), env_at(60), info, msg).unwrap();
+
+        let info = mock_info("minter2", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(20)}]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: None, proof: None };
+        let err = execute(deps.as_mut(), env_at(60), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::MintEnded {}));
+
+        let info = mock_info("minter2", &[]);
+        let msg = ExecuteMsg::MintReserved { nft_address: "collection1".to_string(), recipient: "team".to_string(), quantity: 1 };
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+
+        let info = mock_info("admin1", &[]);
+        let msg = ExecuteMsg::MintReserved { nft_address: "collection1".to_string(), recipient: "team".to_string(), quantity: 5 };
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::MintExceeded {}));
+
+        // reserved tokens can be minted before the sale opens and cost nothing
+        let info = mock_info("admin1", &[]);
+        let msg = ExecuteMsg::MintReserved { nft_address: "collection1".to_string(), recipient: "team".to_string(), quantity: 3 };
+        let res = execute(deps.as_mut(), env_at(1000), info, msg).unwrap();
+        assert_eq!(res.messages.len(),3);
+        match &res.messages[0].msg {
+            CosmosMsg::Wasm(WasmMsg::Execute { msg, .. }) => match from_binary(msg).unwrap() {
+                Cw721BaseExecuteMsg::<Metadata>::Mint(mint) => assert_eq!(mint.owner,"team"),
+                _ => panic!("expected a mint")
+            },
+            _ => panic!("expected a wasm message")
+        }
+
+        // the reserve cannot shrink below what was already minted from it
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::UpdateCollection { members: vec![AdminInfo{
+            address:"admin1".to_string(),
+            portion:Decimal::one()
+        }],
+        nft_address: "collection1".to_string(),
+        collection: CollectionInfo{
+            reserved:Uint128::new(2),
+            ..mock_collection(now, 10)
+        }};
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::WrongNumber {}));
+
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::MintReserved { nft_address: "collection1".to_string(), recipient: "team".to_string(), quantity: 1 };
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let collection_info = query_collection_info(deps.as_ref(), mock_env(), "collection1".to_string(),"user".to_string()).unwrap();
+        assert_eq!(collection_info.info.mint_count,Uint128::new(10));
+        assert_eq!(collection_info.info.reserved_count,Uint128::new(4));
+    }
+
+    fn minted_owners(res:&Response) -> Vec<String> {
+        res.messages.iter().filter_map(|message| match &message.msg {
+            CosmosMsg::Wasm(WasmMsg::Execute { msg, .. }) => match from_binary(msg).unwrap() {
+                Cw721BaseExecuteMsg::<Metadata>::Mint(mint) => Some(mint.owner),
+                _ => None
+            },
+            _ => None
+        }).collect()
+    }
+
+    #[test]
+    fn airdrop() {
+        let mut deps = mock_cw721_dependencies();
+        setup_collection(deps.as_mut(), CollectionInfo{
+            total_nft:Uint128::new(60),
+            ..mock_collection(mock_env().block.time.seconds(), 10)
+        });
+
+        for sender in ["admin1", "artist"].iter() {
+            let info = mock_info(sender, &[]);
+            let msg = ExecuteMsg::Airdrop { nft_address: "collection1".to_string(), recipients: vec![("alice".to_string(),1)] };
+            let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+            assert!(matches!(err, ContractError::Unauthorized {}));
+        }
+
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::Airdrop { nft_address: "collection1".to_string(), recipients: vec![("alice".to_string(),61)] };
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::MintExceeded {}));
+
+        // the first call mints one batch and queues the rest
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::Airdrop { nft_address: "collection1".to_string(), recipients: vec![
+            ("alice".to_string(),30),
+            ("bob".to_string(),25)
+        ] };
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        let owners = minted_owners(&res);
+        assert_eq!(owners.len(),50);
+        assert_eq!(owners.iter().filter(|owner| *owner == "alice").count(),30);
+        assert_eq!(res.attributes[2].value,"5");
+        assert_eq!(AIRDROPS.load(&deps.storage, ("collection1", 1)).unwrap(),("bob".to_string(),5));
+        assert_eq!(AIRDROPQUEUE.load(&deps.storage, "collection1").unwrap(),AirdropQueue{head:1,tail:2,pending:Uint128::new(5)});
+
+        // the sale cannot take the tokens still owed to the queue
+        let info = mock_info("minter1", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(120)}]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: Some(6), proof: None };
+        let err = execute(deps.as_mut(), env_at(60), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::MintExceeded {}));
+
+        let info = mock_info("minter1", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(100)}]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: Some(5), proof: None };
+        execute(deps.as_mut(), env_at(60), info, msg).unwrap();
+
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::Airdrop { nft_address: "collection1".to_string(), recipients: vec![] };
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!(minted_owners(&res),vec!["bob".to_string(); 5]);
+        assert_eq!(res.attributes[2].value,"0");
+        assert!(!AIRDROPQUEUE.has(&deps.storage, "collection1"));
+        assert!(!AIRDROPS.has(&deps.storage, ("collection1", 1)));
+
+        let collection_info = query_collection_info(deps.as_ref(), mock_env(), "collection1".to_string(),"user".to_string()).unwrap();
+        assert_eq!(collection_info.info.mint_count,Uint128::new(60));
+    }
+
+    #[test]
+    fn creator_manages_collection() {
+        let mut deps = mock_cw721_dependencies();
+        let now = mock_env().block.time.seconds();
+        setup_collection(deps.as_mut(), mock_collection(now, 10));
+
+        let info = mock_info("artist", &[]);
+        let msg = ExecuteMsg::AddWhiteUsers { address: "collection1".to_string(), white_users: vec![WhiteUserInfo{
+            address:"minter1".to_string(),
+            count:Uint128::new(1)
+        }] };
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let info = mock_info("stranger", &[]);
+        let msg = ExecuteMsg::SetMintFlag { address: "collection1".to_string(), time: now + 10 };
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+
+        // the creator edits payouts but cannot hand the collection over
+        let info = mock_info("artist", &[]);
+        let msg = ExecuteMsg::UpdateCollection { members: vec![AdminInfo{
+            address:"artist".to_string(),
+            portion:Decimal::one()
+        }],
+        nft_address: "collection1".to_string(),
+        collection: CollectionInfo{
+            creator:"someone".to_string(),
+            ..mock_collection(now, 10)
+        }};
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!(query_admin_info(deps.as_ref(), "collection1".to_string()).unwrap()[0].address,"artist");
+        let collection_info = query_collection_info(deps.as_ref(), mock_env(), "collection1".to_string(),"user".to_string()).unwrap();
+        assert_eq!(collection_info.info.creator,"artist");
+
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::UpdateCollection { members: vec![AdminInfo{
+            address:"artist".to_string(),
+            portion:Decimal::one()
+        }],
+        nft_address: "collection1".to_string(),
+        collection: CollectionInfo{
+            creator:"someone".to_string(),
+            ..mock_collection(now, 10)
+        }};
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let info = mock_info("artist", &[]);
+        let msg = ExecuteMsg::RemoveWhiteUsers { address: "collection1".to_string(), white_users: vec!["minter1".to_string()] };
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+
+        let info = mock_info("someone", &[]);
+        let msg = ExecuteMsg::RemoveWhiteUsers { address: "collection1".to_string(), white_users: vec!["minter1".to_string()] };
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    }
+
+    /// Answers as a cw721 created by "artist" whose minter is this contract.
+    struct Cw721Querier {
+        base: MockQuerier,
+        minter: String,
+        num_tokens: u64
+    }
+
+    impl Querier for Cw721Querier {
+        fn raw_query(&self, bin_request: &[u8]) -> QuerierResult {
+            match from_slice(bin_request) {
+                Ok(QueryRequest::<Empty>::Wasm(WasmQuery::ContractInfo { .. })) => {
+                    SystemResult::Ok(ContractResult::Ok(to_binary(&ContractInfoResponse::new(1, "artist")).unwrap()))
+                },
+                Ok(QueryRequest::<Empty>::Wasm(WasmQuery::Smart { msg, .. })) => {
+                    let response = match from_binary(&msg).unwrap() {
+                        Cw721QueryMsg::Minter {} => to_binary(&MinterResponse{minter:self.minter.clone()}),
+                        Cw721QueryMsg::NumTokens {} => to_binary(&NumTokensResponse{count:self.num_tokens}),
+                        _ => Err(StdError::generic_err("unsupported cw721 query"))
+                    };
+                    SystemResult::Ok(response.into())
+                },
+                _ => self.base.raw_query(bin_request)
+            }
+        }
+    }
+
+    fn mock_cw721_dependencies() -> OwnedDeps<MockStorage, MockApi, Cw721Querier> {
+        OwnedDeps {
+            storage: MockStorage::default(),
+            api: MockApi::default(),
+            querier: Cw721Querier{base: MockQuerier::default(), minter: MOCK_CONTRACT_ADDR.to_string(), num_tokens: 0},
+            custom_query_type: PhantomData
+        }
+    }
+
+    #[test]
+    fn self_registration() {
+        let mut deps = mock_cw721_dependencies();
+        let now = mock_env().block.time.seconds();
+
+        let info = mock_info("creator", &[]);
+        instantiate(deps.as_mut(), mock_env(), info, InstantiateMsg{owner:"creator".to_string()}).unwrap();
+
+        let info = mock_info("artist", &[]);
+        let err = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::SetListingFee { fee: None }).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+
+        let info = mock_info("creator", &[]);
+        execute(deps.as_mut(), mock_env(), info, ExecuteMsg::SetListingFee { fee: Some(Coin{denom:"ujunox".to_string(),amount:Uint128::new(100)}) }).unwrap();
+        let info = mock_info("creator", &[]);
+        execute(deps.as_mut(), mock_env(), info, ExecuteMsg::SetTreasury { address: "treasury".to_string() }).unwrap();
+
+        let register = ExecuteMsg::AddCollection { members: vec![AdminInfo{
+            address:"artist".to_string(),
+            portion:Decimal::one()
+        }],
+        nft_address: "collection1".to_string(),
+        collection: CollectionInfo{
+            creator:"someone".to_string(),
+            ..mock_collection(now, 10)
+        },
+        token_ids: None};
+
+        let info = mock_info("stranger", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(100)}]);
+        let err = execute(deps.as_mut(), mock_env(), info, register.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::NotCollectionOwner {}));
+
+        let info = mock_info("artist", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(50)}]);
+        let err = execute(deps.as_mut(), mock_env(), info, register.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Notenough {}));
+
+        let info = mock_info("artist", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(120)}]);
+        let res = execute(deps.as_mut(), mock_env(), info, register).unwrap();
+        assert_eq!(res.messages.len(),2);
+        assert_eq!(res.messages[0].msg,CosmosMsg::Bank(BankMsg::Send {
+                to_address: "treasury".to_string(),
+                amount:vec![Coin{denom:"ujunox".to_string(),amount:Uint128::new(100)}]
+        }));
+
+        // the registrant manages the collection, but it stays closed until approved
+        let collection_info = query_collection_info(deps.as_ref(), env_at(60), "collection1".to_string(),"user".to_string()).unwrap();
+        assert_eq!(collection_info.info.creator,"artist");
+        assert!(!collection_info.info.can_mint);
+
+        let info = mock_info("minter1", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(20)}]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: None, proof: None };
+        let err = execute(deps.as_mut(), env_at(60), info, msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::CollectionNotApproved {}));
+
+        let info = mock_info("artist", &[]);
+        let err = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::ApproveCollection { nft_address: "collection1".to_string() }).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+
+        let info = mock_info("creator", &[]);
+        execute(deps.as_mut(), mock_env(), info, ExecuteMsg::ApproveCollection { nft_address: "collection1".to_string() }).unwrap();
+
+        let info = mock_info("minter1", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(20)}]);
+        execute(deps.as_mut(), env_at(60), info, msg.clone()).unwrap();
+
+        // a live collection can be delisted but not rejected
+        let info = mock_info("creator", &[]);
+        let err = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::RejectCollection { nft_address: "collection1".to_string() }).unwrap_err();
+        assert!(matches!(err, ContractError::CollectionLive {}));
+
+        let info = mock_info("creator", &[]);
+        execute(deps.as_mut(), mock_env(), info, ExecuteMsg::DelistCollection { nft_address: "collection1".to_string() }).unwrap();
+        let info = mock_info("minter1", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(20)}]);
+        let err = execute(deps.as_mut(), env_at(60), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::CollectionNotApproved {}));
+
+        // a rejected registration frees the address
+        let info = mock_info("artist", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(100)}]);
+        let msg = ExecuteMsg::AddCollection { members: vec![AdminInfo{
+            address:"artist".to_string(),
+            portion:Decimal::one()
+        }],
+        nft_address: "collection2".to_string(),
+        collection: mock_collection(now, 10),
+        token_ids: Some((1..=10).rev().collect())};
+        execute(deps.as_mut(), mock_env(), info, msg.clone()).unwrap();
+
+        let info = mock_info("creator", &[]);
+        execute(deps.as_mut(), mock_env(), info, ExecuteMsg::RejectCollection { nft_address: "collection2".to_string() }).unwrap();
+        assert!(query_collection_info(deps.as_ref(), mock_env(), "collection2".to_string(),"user".to_string()).is_err());
+        assert!(TOKENPOOL.prefix("collection2").keys(&deps.storage, None, None, Order::Ascending).next().is_none());
+
+        let info = mock_info("artist", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(100)}]);
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    }
+
+    #[test]
+    fn platform_fee() {
+        let mut deps = mock_cw721_dependencies();
+        setup_collection(deps.as_mut(), mock_collection(mock_env().block.time.seconds(), 10));
+
+        let info = mock_info("creator", &[]);
+        let err = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::SetPlatformFee { fee_bps: 10_001 }).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidFee {}));
+
+        let info = mock_info("admin1", &[]);
+        let err = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::SetPlatformFee { fee_bps: 500 }).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+
+        let info = mock_info("creator", &[]);
+        execute(deps.as_mut(), mock_env(), info, ExecuteMsg::SetPlatformFee { fee_bps: 500 }).unwrap();
+        let info = mock_info("creator", &[]);
+        execute(deps.as_mut(), mock_env(), info, ExecuteMsg::SetTreasury { address: "treasury".to_string() }).unwrap();
+        let state = query_state_info(deps.as_ref()).unwrap();
+        assert_eq!(state.platform_fee_bps,500);
+        assert_eq!(state.treasury,"treasury");
+
+        // 5% of 200, then 70/30 of the remaining 190
+        let info = mock_info("minter1", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(200)}]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: Some(10), proof: None };
+        let res = execute(deps.as_mut(), env_at(60), info, msg).unwrap();
+        assert_eq!(res.attributes[1].value,"10ujunox");
+        assert_eq!(res.messages[10].msg,CosmosMsg::Bank(BankMsg::Send {
+                to_address: "treasury".to_string(),
+                amount:vec![Coin{denom:"ujunox".to_string(),amount:Uint128::new(10)}]
+        }));
+        assert_eq!(res.messages[11].msg,CosmosMsg::Bank(BankMsg::Send {
+                to_address: "admin1".to_string(),
+                amount:vec![Coin{denom:"ujunox".to_string(),amount:Uint128::new(133)}]
+        }));
+        assert_eq!(res.messages[12].msg,CosmosMsg::Bank(BankMsg::Send {
+                to_address: "admin2".to_string(),
+                amount:vec![Coin{denom:"ujunox".to_string(),amount:Uint128::new(57)}]
+        }));
+    }
+
+    #[test]
+    fn pull_payouts() {
+        let mut deps = mock_cw721_dependencies();
+        let now = mock_env().block.time.seconds();
+        setup_collection(deps.as_mut(), CollectionInfo{
+            auto_payout:false,
+            ..mock_collection(now, 10)
+        });
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::AddCollection { members: vec![AdminInfo{
+            address:"admin1".to_string(),
+            portion:Decimal::one()
+        }],
+        nft_address: "collection2".to_string(),
+        collection: CollectionInfo{
+            auto_payout:false,
+            phases:vec![mock_phase("public", now, now + 100, 5)],
+            ..mock_collection(now, 10)
+        },
+        token_ids: None};
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // proceeds stay in the contract, only the mints and the refund go out
+        let info = mock_info("minter1", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(50)}]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: Some(2), proof: None };
+        let res = execute(deps.as_mut(), env_at(60), info, msg).unwrap();
+        assert_eq!(res.messages.len(),3);
+
+        let info = mock_info("minter1", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(15)}]);
+        let msg = ExecuteMsg::Mint { address: "collection2".to_string(), quantity: Some(3), proof: None };
+        execute(deps.as_mut(), env_at(60), info, msg).unwrap();
+
+        let pending = query_pending_payouts(deps.as_ref(), "admin1".to_string(), None).unwrap();
+        assert_eq!(pending.payouts,vec![
+            PendingPayout{nft_address:"collection1".to_string(),denom:"ujunox".to_string(),amount:Uint128::new(28)},
+            PendingPayout{nft_address:"collection2".to_string(),denom:"ujunox".to_string(),amount:Uint128::new(15)}
+        ]);
+        let pending = query_pending_payouts(deps.as_ref(), "admin2".to_string(), Some("collection1".to_string())).unwrap();
+        assert_eq!(pending.payouts[0].amount,Uint128::new(12));
+
+        let info = mock_info("admin2", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Withdraw { nft_address: "collection1".to_string() }).unwrap();
+        assert_eq!(res.messages[0].msg,CosmosMsg::Bank(BankMsg::Send {
+                to_address: "admin2".to_string(),
+                amount:vec![Coin{denom:"ujunox".to_string(),amount:Uint128::new(12)}]
+        }));
+        let info = mock_info("admin2", &[]);
+        let err = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Withdraw { nft_address: "collection1".to_string() }).unwrap_err();
+        assert!(matches!(err, ContractError::NothingToWithdraw {}));
+
+        let info = mock_info("admin1", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::WithdrawAll {  }).unwrap();
+        assert_eq!(res.messages[0].msg,CosmosMsg::Bank(BankMsg::Send {
+                to_address: "admin1".to_string(),
+                amount:vec![Coin{denom:"ujunox".to_string(),amount:Uint128::new(43)}]
+        }));
+        assert!(query_pending_payouts(deps.as_ref(), "admin1".to_string(), None).unwrap().payouts.is_empty());
+    }
+
+    #[test]
+    fn exact_payout_split() {
+        let third = Decimal::from_ratio(1u128, 3u128);
+        let thirds = vec![
+            AdminInfo{address:"admin1".to_string(),portion:third},
+            AdminInfo{address:"admin2".to_string(),portion:third},
+            AdminInfo{address:"admin3".to_string(),portion:Decimal::one() - third - third}
+        ];
+        assert_eq!(split_shares(&thirds, Uint128::new(10)),vec![
+            ("admin1".to_string(),Uint128::new(4)),
+            ("admin2".to_string(),Uint128::new(3)),
+            ("admin3".to_string(),Uint128::new(3))
+        ]);
+
+        let mut deps = mock_cw721_dependencies();
+        let now = mock_env().block.time.seconds();
+        let info = mock_info("creator", &[]);
+        instantiate(deps.as_mut(), mock_env(), info, InstantiateMsg{owner:"creator".to_string()}).unwrap();
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::AddCollection { members: thirds,
+        nft_address: "collection1".to_string(),
+        collection: CollectionInfo{
+            auto_payout:false,
+            phases:vec![mock_phase("public", now, now + 100, 5)],
+            ..mock_collection(now, 10)
+        },
+        token_ids: None};
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let info = mock_info("minter1", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(10)}]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: Some(2), proof: None };
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        let pending = query_pending_payouts(deps.as_ref(), "admin1".to_string(), None).unwrap();
+        assert_eq!(pending.payouts[0].amount,Uint128::new(4));
+
+        // anything beyond the owed payouts, like a stray transfer, can be swept to the treasury
+        deps.querier.base.update_balance(MOCK_CONTRACT_ADDR, vec![
+            Coin{denom:"ujunox".to_string(),amount:Uint128::new(12)},
+            Coin{denom:"uatom".to_string(),amount:Uint128::new(5)}
+        ]);
+        let balances = query_balances(deps.as_ref(), mock_env()).unwrap();
+        assert_eq!(balances.balances,vec![
+            BalanceInfo{denom:"ujunox".to_string(),balance:Uint128::new(12),owed:Uint128::new(10),dust:Uint128::new(2)},
+            BalanceInfo{denom:"uatom".to_string(),balance:Uint128::new(5),owed:Uint128::zero(),dust:Uint128::new(5)}
+        ]);
+
+        let info = mock_info("admin1", &[]);
+        let err = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::SweepDust {  }).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+
+        let info = mock_info("creator", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::SweepDust {  }).unwrap();
+        assert_eq!(res.messages[0].msg,CosmosMsg::Bank(BankMsg::Send {
+                to_address: "creator".to_string(),
+                amount:vec![
+                    Coin{denom:"ujunox".to_string(),amount:Uint128::new(2)},
+                    Coin{denom:"uatom".to_string(),amount:Uint128::new(5)}
+                ]
+        }));
+
+        let info = mock_info("admin1", &[]);
+        execute(deps.as_mut(), mock_env(), info, ExecuteMsg::WithdrawAll {  }).unwrap();
+        deps.querier.base.update_balance(MOCK_CONTRACT_ADDR, vec![Coin{denom:"ujunox".to_string(),amount:Uint128::new(6)}]);
+        let balances = query_balances(deps.as_ref(), mock_env()).unwrap();
+        assert_eq!(balances.balances[0].owed,Uint128::new(6));
+        assert_eq!(balances.balances[0].dust,Uint128::zero());
+    }
+
+    #[test]
+    fn odd_price_payouts() {
+        let third = Decimal::from_ratio(1u128, 3u128);
+        let thirds = vec![
+            AdminInfo{address:"admin1".to_string(),portion:third},
+            AdminInfo{address:"admin2".to_string(),portion:third},
+            AdminInfo{address:"admin3".to_string(),portion:Decimal::one() - third - third}
+        ];
+        let mut deps = mock_cw721_dependencies();
+        let now = mock_env().block.time.seconds();
+        let info = mock_info("creator", &[]);
+        instantiate(deps.as_mut(), mock_env(), info, InstantiateMsg{owner:"creator".to_string()}).unwrap();
+        for (nft_address, price) in [("collection1", 1), ("collection2", 7)].iter() {
+            let info = mock_info("creator", &[]);
+            let msg = ExecuteMsg::AddCollection { members: thirds.clone(),
+            nft_address: nft_address.to_string(),
+            collection: CollectionInfo{
+                auto_payout:false,
+                phases:vec![mock_phase("public", now, now + 100, *price)],
+                ..mock_collection(now, 10)
+            },
+            token_ids: None};
+            execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        }
+
+        // a price of one leaves the other admins nothing, which is not recorded as a payout
+        let info = mock_info("minter1", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(1)}]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: None, proof: None };
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert!(query_pending_payouts(deps.as_ref(), "admin2".to_string(), None).unwrap().payouts.is_empty());
+
+        let info = mock_info("admin2", &[]);
+        let err = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Withdraw { nft_address: "collection1".to_string() }).unwrap_err();
+        assert!(matches!(err, ContractError::NothingToWithdraw {}));
+
+        let info = mock_info("minter1", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(7)}]);
+        let msg = ExecuteMsg::Mint { address: "collection2".to_string(), quantity: None, proof: None };
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let info = mock_info("admin2", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::WithdrawAll {  }).unwrap();
+        assert_eq!(res.messages[0].msg,CosmosMsg::Bank(BankMsg::Send {
+                to_address: "admin2".to_string(),
+                amount:vec![Coin{denom:"ujunox".to_string(),amount:Uint128::new(2)}]
+        }));
+
+        let info = mock_info("admin1", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::WithdrawAll {  }).unwrap();
+        assert_eq!(res.messages[0].msg,CosmosMsg::Bank(BankMsg::Send {
+                to_address: "admin1".to_string(),
+                amount:vec![Coin{denom:"ujunox".to_string(),amount:Uint128::new(4)}]
+        }));
+    }
+
+    #[test]
+    fn royalties_in_extension() {
+        let mut deps = mock_cw721_dependencies();
+        let now = mock_env().block.time.seconds();
+        setup_collection(deps.as_mut(), CollectionInfo{
+            royalty:Some(Royalty{percentage:5, payment_address:"artist".to_string()}),
+            ..mock_collection(now, 10)
+        });
+
+        let info = mock_info("minter1", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(20)}]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: None, proof: None };
+        let res = execute(deps.as_mut(), env_at(60), info, msg).unwrap();
+        match &res.messages[0].msg {
+            CosmosMsg::Wasm(WasmMsg::Execute { msg, .. }) => match from_binary(msg).unwrap() {
+                Cw721BaseExecuteMsg::<Metadata>::Mint(mint) => {
+                    assert_eq!(mint.extension.royalty_percentage,Some(5));
+                    assert_eq!(mint.extension.royalty_payment_address,Some("artist".to_string()));
+                },
+                _ => panic!("expected a mint")
+            },
+            _ => panic!("expected a wasm message")
+        }
+
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::UpdateCollection { members: vec![AdminInfo{
+            address:"admin1".to_string(),
+            portion:Decimal::one()
+        }],
+        nft_address: "collection1".to_string(),
+        collection: CollectionInfo{
+            royalty:Some(Royalty{percentage:101, payment_address:"artist".to_string()}),
+            ..mock_collection(now, 10)
+        }};
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidRoyalty {}));
+    }
+
+    #[test]
+    fn metadata_template() {
+        let mut deps = mock_cw721_dependencies();
+        let now = mock_env().block.time.seconds();
+        setup_collection(deps.as_mut(), CollectionInfo{
+            metadata:Some(MetadataTemplate{
+                name:Some("Punk #{n}".to_string()),
+                description:Some("One of ten punks".to_string()),
+                animation_url:Some("ipfs://cid/{n}.mp4".to_string()),
+                background_color:Some("ffffff".to_string()),
+                ..MetadataTemplate::default()
+            }),
+            ..mock_collection(now, 10)
+        });
+
+        let info = mock_info("minter1", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(20)}]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: None, proof: None };
+        let res = execute(deps.as_mut(), env_at(60), info, msg).unwrap();
+        match &res.messages[0].msg {
+            CosmosMsg::Wasm(WasmMsg::Execute { msg, .. }) => match from_binary(msg).unwrap() {
+                Cw721BaseExecuteMsg::<Metadata>::Mint(mint) => {
+                    let n = mint.token_id.trim_start_matches("Collection1.");
+                    assert_eq!(mint.extension,Metadata{
+                        image:Some(format!("imag_url{}.png",n)),
+                        name:Some(format!("Punk #{}",n)),
+                        description:Some("One of ten punks".to_string()),
+                        animation_url:Some(format!("ipfs://cid/{}.mp4",n)),
+                        background_color:Some("ffffff".to_string()),
+                        ..Metadata::default()
+                    });
+                },
+                _ => panic!("expected a mint")
+            },
+            _ => panic!("expected a wasm message")
+        }
+    }
+
+    #[test]
+    fn trait_upload() {
+        let mut deps = mock_cw721_dependencies();
+        let now = mock_env().block.time.seconds();
+        setup_collection(deps.as_mut(), mock_collection(now, 10));
+
+        let traits = |n:u32| vec![Trait{display_type:None,trait_type:"hat".to_string(),value:format!("hat {}",n)}];
+
+        let info = mock_info("minter1", &[]);
+        let msg = ExecuteMsg::UploadTraits { nft_address: "collection1".to_string(), traits: vec![(1, traits(1))] };
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::UploadTraits { nft_address: "collection1".to_string(), traits: (1..=101).map(|n| (n, traits(n))).collect() };
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::WrongNumber {}));
+
+        // only numbers the collection can mint take traits
+        for number in [0, 11].iter() {
+            let info = mock_info("creator", &[]);
+            let msg = ExecuteMsg::UploadTraits { nft_address: "collection1".to_string(), traits: vec![(*number, traits(*number))] };
+            let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+            assert!(matches!(err, ContractError::WrongNumber {}));
+        }
+
+        for batch in [1..=5, 6..=10] {
+            let info = mock_info("creator", &[]);
+            let msg = ExecuteMsg::UploadTraits { nft_address: "collection1".to_string(), traits: batch.map(|n| (n, traits(n))).collect() };
+            execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        }
+        assert_eq!(query_token_traits(deps.as_ref(), "collection1".to_string(), 7).unwrap(),Some(traits(7)));
+
+        let info = mock_info("minter1", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(20)}]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: None, proof: None };
+        let res = execute(deps.as_mut(), env_at(60), info, msg).unwrap();
+        match &res.messages[0].msg {
+            CosmosMsg::Wasm(WasmMsg::Execute { msg, .. }) => match from_binary(msg).unwrap() {
+                Cw721BaseExecuteMsg::<Metadata>::Mint(mint) => {
+                    let n:u32 = mint.token_id.trim_start_matches("Collection1.").parse().unwrap();
+                    assert_eq!(mint.extension.attributes,Some(traits(n)));
+                },
+                _ => panic!("expected a mint")
+            },
+            _ => panic!("expected a wasm message")
+        }
+
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::UploadTraits { nft_address: "collection1".to_string(), traits: vec![(1, vec![])] };
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::MetadataLocked {}));
+
+        // with an explicit id list the listed ids take traits instead
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::AddCollection { members: vec![AdminInfo{
+            address:"admin1".to_string(),
+            portion:Decimal::one()
+        }],
+        nft_address: "collection2".to_string(),
+        collection: mock_collection(now, 10),
+        token_ids: Some((101..=110).collect())};
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::UploadTraits { nft_address: "collection2".to_string(), traits: vec![(3, traits(3))] };
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::WrongNumber {}));
+
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::UploadTraits { nft_address: "collection2".to_string(), traits: vec![(105, traits(105))] };
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    }
+
+    #[test]
+    fn token_templates() {
+        let mut deps = mock_cw721_dependencies();
+        let now = mock_env().block.time.seconds();
+        let info = mock_info("creator", &[]);
+        instantiate(deps.as_mut(), mock_env(), info, InstantiateMsg{owner:"creator".to_string()}).unwrap();
+
+        let templated = |token_id:&str, token_uri:&str| CollectionInfo{
+            url:"ipfs://cid/".to_string(),
+            templates:Some(TokenTemplates{
+                token_id:token_id.to_string(),
+                token_uri:token_uri.to_string(),
+                image:Some("ipfs://art/{n}.gif".to_string()),
+                padding:Some(3)
+            }),
+            ..mock_collection(now, 10)
+        };
+
+        for collection in [templated("{name}", "{url}{n}"), templated("{name} #{n}", "{url}{cid}/{n}"), templated("{name} #{n}", "{url{n}")] {
+            let info = mock_info("creator", &[]);
+            let msg = ExecuteMsg::AddCollection { members: vec![AdminInfo{
+                address:"admin1".to_string(),
+                portion:Decimal::one()
+            }],
+            nft_address: "collection1".to_string(),
+            collection,
+            token_ids: None};
+            let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+            assert!(matches!(err, ContractError::InvalidTemplate { .. }));
+        }
+
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::AddCollection { members: vec![AdminInfo{
+            address:"admin1".to_string(),
+            portion:Decimal::one()
+        }],
+        nft_address: "collection1".to_string(),
+        collection: templated("{name} #{n}", "{url}{n}"),
+        token_ids: None};
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let token = query_token_uri(deps.as_ref(), "collection1".to_string(), 7).unwrap();
+        assert_eq!(token.token_uri,"ipfs://cid/007");
+        assert_eq!(token.image,Some("ipfs://art/007.gif".to_string()));
+
+        let info = mock_info("minter1", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(20)}]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: None, proof: None };
+        let res = execute(deps.as_mut(), env_at(60), info, msg).unwrap();
+        let token_id = &minted_token_ids(&res)[0];
+        assert!(token_id.starts_with("Collection1 #0"));
+        assert_eq!(token_id.len(),"Collection1 #000".len());
+
+        // ids cannot be renamed after the first mint
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::UpdateCollection { members: vec![AdminInfo{
+            address:"admin1".to_string(),
+            portion:Decimal::one()
+        }],
+        nft_address: "collection1".to_string(),
+        collection: templated("Punk {n}", "{url}{n}")};
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::MetadataLocked {}));
+    }
+
+    #[test]
+    fn batch_mint() {
+        let mut deps = mock_cw721_dependencies();
+        setup_collection(deps.as_mut(), mock_collection(mock_env().block.time.seconds(), 3));
+
+        let info = mock_info("minter1", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(60)}]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: Some(3), proof: None };
+        let res = execute(deps.as_mut(), env_at(60), info, msg).unwrap();
+        assert_eq!(res.messages.len(),5);
+        assert_eq!(res.messages[3].msg,CosmosMsg::Bank(BankMsg::Send {
+                to_address: "admin1".to_string(),
+                amount:vec![Coin{denom:"ujunox".to_string(),amount:Uint128::new(42)}]
+        }));
+        assert_eq!(res.messages[4].msg,CosmosMsg::Bank(BankMsg::Send {
+                to_address: "admin2".to_string(),
+                amount:vec![Coin{denom:"ujunox".to_string(),amount:Uint128::new(18)}]
+        }));
+
+        let user_info = query_user_info(deps.as_ref(), "collection1".to_string(), "minter1".to_string()).unwrap();
+        assert_eq!(user_info,Uint128::new(3));
+        let collection_info = query_collection_info(deps.as_ref(), env_at(60), "collection1".to_string(),"user".to_string()).unwrap();
+        assert_eq!(collection_info.info.mint_count,Uint128::new(3));
+
+
+        // price is charged for the whole batch
+        let info = mock_info("minter2", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(20)}]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: Some(2), proof: None };
+        let err = execute(deps.as_mut(), env_at(60), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::Notenough {}));
+
+        // whitelist allocation is checked against the whole batch
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::AddWhiteUsers { address: "collection1".to_string(), white_users: vec![WhiteUserInfo{
+            address:"minter2".to_string(),
+            count:Uint128::new(2)
+        }] };
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let info = mock_info("minter2", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(30)}]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: Some(3), proof: None };
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::MintExceeded {}));
+
+        let info = mock_info("minter2", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(20)}]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: Some(2), proof: None };
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!(res.messages.len(),4);
+
+        // cannot mint more than what is left
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::AddFreeMinter { address: "collection1".to_string(), minters: vec!["minter3".to_string()] };
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let info = mock_info("minter3", &[]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: Some(6), proof: None };
+        let err = execute(deps.as_mut(), env_at(110), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::MintExceeded {}));
+
+        let info = mock_info("minter3", &[]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: Some(5), proof: None };
+        let res = execute(deps.as_mut(), env_at(110), info, msg).unwrap();
+        assert_eq!(res.messages.len(),5);
+        let collection_info = query_collection_info(deps.as_ref(), mock_env(), "collection1".to_string(),"user".to_string()).unwrap();
+        assert_eq!(collection_info.info.mint_count,Uint128::new(10));
+    }
+
+    #[test]
+    fn overpayment_is_refunded() {
+        let mut deps = mock_cw721_dependencies();
+        setup_collection(deps.as_mut(), mock_collection(mock_env().block.time.seconds(), 3));
+
+        let info = mock_info("minter1", &[
+            Coin{denom:"ujunox".to_string(),amount:Uint128::new(25)},
+            Coin{denom:"uatom".to_string(),amount:Uint128::new(7)}
+        ]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: None, proof: None };
+        let res = execute(deps.as_mut(), env_at(60), info, msg).unwrap();
+        assert_eq!(res.messages.len(),4);
+        assert_eq!(res.messages[3].msg,CosmosMsg::Bank(BankMsg::Send {
+                to_address: "minter1".to_string(),
+                amount:vec![
+                    Coin{denom:"ujunox".to_string(),amount:Uint128::new(5)},
+                    Coin{denom:"uatom".to_string(),amount:Uint128::new(7)}
+                ]
+        }));
+
+        // exact payment sends nothing back
+        let info = mock_info("minter1", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(20)}]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: None, proof: None };
+        let res = execute(deps.as_mut(), env_at(60), info, msg).unwrap();
+        assert_eq!(res.messages.len(),3);
+
+        let info = mock_info("minter2", &[Coin{denom:"uatom".to_string(),amount:Uint128::new(20)}]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: None, proof: None };
+        let err = execute(deps.as_mut(), env_at(60), info, msg).unwrap_err();
+        match err {
+            ContractError::WrongDenom { expected, received } => {
+                assert_eq!(expected,"20ujunox");
+                assert_eq!(received,"20uatom");
+            },
+            e => panic!("unexpected error: {:?}", e)
+        }
+
+        let info = mock_info("minter2", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(19)}]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: None, proof: None };
+        let err = execute(deps.as_mut(), env_at(60), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::Notenough {}));
+
+        // free mints hand back whatever was sent
+        let info = mock_info("minter2", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(20)}]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: None, proof: None };
+        let res = execute(deps.as_mut(), env_at(110), info, msg).unwrap();
+        assert_eq!(res.messages.len(),2);
+        assert_eq!(res.messages[1].msg,CosmosMsg::Bank(BankMsg::Send {
+                to_address: "minter2".to_string(),
+                amount:vec![Coin{denom:"ujunox".to_string(),amount:Uint128::new(20)}]
+        }));
+    }
+
+    #[test]
+    fn mint_caps_per_phase() {
+        let mut deps = mock_cw721_dependencies();
+        setup_collection(deps.as_mut(), mock_collection(mock_env().block.time.seconds(), 2));
+
+        // public: a first mint above max_nft is rejected, exactly max_nft is allowed
+        let info = mock_info("minter1", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(60)}]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: Some(3), proof: None };
+        let err = execute(deps.as_mut(), env_at(60), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::MintExceeded {}));
+
+        let info = mock_info("minter1", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(20)}]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: None, proof: None };
+        execute(deps.as_mut(), env_at(60), info.clone(), msg.clone()).unwrap();
+        execute(deps.as_mut(), env_at(60), info.clone(), msg.clone()).unwrap();
+        let err = execute(deps.as_mut(), env_at(60), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::MintExceeded {}));
+
+        // a failed payment is not counted against the wallet
+        let info = mock_info("minter2", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(10)}]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: None, proof: None };
+        let err = execute(deps.as_mut(), env_at(60), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::Notenough {}));
+        let user_info = query_user_info(deps.as_ref(), "collection1".to_string(), "minter2".to_string()).unwrap();
+        assert_eq!(user_info,Uint128::new(0));
+
+        // private: the whitelist allocation is the cap
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::AddWhiteUsers { address: "collection1".to_string(), white_users: vec![WhiteUserInfo{
+            address:"minter2".to_string(),
+            count:Uint128::new(2)
+        }] };
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let info = mock_info("minter2", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(20)}]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: Some(2), proof: None };
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        let info = mock_info("minter2", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(10)}]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: None, proof: None };
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::MintExceeded {}));
+
+        // free: max_nft still applies, free minters are exempt
+        let info = mock_info("minter3", &[]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: Some(3), proof: None };
+        let err = execute(deps.as_mut(), env_at(110), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::MintExceeded {}));
+
+        let info = mock_info("minter3", &[]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: Some(2), proof: None };
+        execute(deps.as_mut(), env_at(110), info, msg).unwrap();
+        let info = mock_info("minter3", &[]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: None, proof: None };
+        let err = execute(deps.as_mut(), env_at(110), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::MintExceeded {}));
+
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::AddFreeMinter { address: "collection1".to_string(), minters: vec!["minter3".to_string()] };
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let info = mock_info("minter3", &[]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: None, proof: None };
+        let res = execute(deps.as_mut(), env_at(110), info, msg).unwrap();
+        assert_eq!(res.messages.len(),1);
+    }
+
+    #[test]
+    fn delayed_reveal() {
+        let mut deps = mock_cw721_dependencies();
+        let now = mock_env().block.time.seconds();
+
+        let info = mock_info("creator", &[]);
+        instantiate(deps.as_mut(), mock_env(), info, InstantiateMsg{owner:"creator".to_string()}).unwrap();
+
+        // hidden collections must commit to a provenance hash
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::AddCollection { members: vec![AdminInfo{
+            address:"admin1".to_string(),
+            portion:Decimal::one()
+        }],
+        nft_address: "collection1".to_string(),
+        collection: CollectionInfo{
+            placeholder_url:Some("hidden.json".to_string()),
+            ..mock_collection(now, 10)
+        },
+        token_ids: None};
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::NoProvenance {}));
+
+        let provenance = "a".repeat(64);
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::AddCollection { members: vec![AdminInfo{
+            address:"admin1".to_string(),
+            portion:Decimal::one()
+        }],
+        nft_address: "collection1".to_string(),
+        collection: CollectionInfo{
+            placeholder_url:Some("hidden/{n}.json".to_string()),
+            provenance_hash:Some(provenance.clone()),
+            ..mock_collection(now, 10)
+        },
+        token_ids: None};
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
 
+        let info = mock_info("minter1", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(100)}]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: Some(5), proof: None };
+        let res = execute(deps.as_mut(), env_at(60), info, msg).unwrap();
+        match &res.messages[0].msg {
+            CosmosMsg::Wasm(WasmMsg::Execute { msg, .. }) => {
+                let msg:Cw721BaseExecuteMsg<Metadata> = from_binary(msg).unwrap();
+                match msg {
+                    Cw721BaseExecuteMsg::Mint(mint) => {
+                        let number = mint.token_id.trim_start_matches("Collection1.");
+                        assert_eq!(mint.token_uri,Some(format!("hidden/{}.json",number)));
+                        assert_eq!(mint.extension.image,None);
+                    },
+                    _ => panic!("expected a mint")
+                }
+            },
+            _ => panic!("expected a wasm message")
+        }
 
+        // reveal settings are frozen once minting started
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::UpdateCollection { members: vec![AdminInfo{
+            address:"admin1".to_string(),
+            portion:Decimal::one()
+        }],
+        nft_address: "collection1".to_string(),
+        collection: CollectionInfo{
+            provenance_hash:Some(provenance),
+            ..mock_collection(now, 10)
+        }};
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::RevealLocked {}));
 
-#[entry_point]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
-    match msg {
-        QueryMsg::GetStateInfo {} => to_binary(& query_state_info(deps)?),
-        QueryMsg::GetAdminInfo { nft_address }=>to_binary(& query_admin_info(deps,nft_address)?),
-        QueryMsg::GetUserInfo {nft_address, address }=>to_binary(& query_user_info(deps,nft_address,address)?),
-        QueryMsg::GetCollectionInfo { nft_address,address }=>to_binary(& query_collection_info(deps,nft_address,address)?)
+        let provenance_info = query_provenance(deps.as_ref(), "collection1".to_string()).unwrap();
+        assert_eq!(provenance_info.provenance_hash,Some("a".repeat(64)));
+        assert!(provenance_info.locked);
+
+        let info = mock_info("creator", &[]);
+        let err = execute(deps.as_mut(), env_at(60), info, ExecuteMsg::Reveal { address: "collection1".to_string() }).unwrap_err();
+        assert!(matches!(err, ContractError::RevealNotReady {}));
+
+        let info = mock_info("minter1", &[]);
+        let err = execute(deps.as_mut(), env_at(150), info, ExecuteMsg::Reveal { address: "collection1".to_string() }).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+
+        let token = query_token_uri(deps.as_ref(), "collection1".to_string(), 3).unwrap();
+        assert_eq!(token.token_uri,"hidden/3.json");
+
+        // the sale window closed, so the collection can be revealed once
+        let info = mock_info("creator", &[]);
+        execute(deps.as_mut(), env_at(150), info, ExecuteMsg::Reveal { address: "collection1".to_string() }).unwrap();
+        let info = mock_info("creator", &[]);
+        let err = execute(deps.as_mut(), env_at(150), info, ExecuteMsg::Reveal { address: "collection1".to_string() }).unwrap_err();
+        assert!(matches!(err, ContractError::AlreadyRevealed {}));
+
+        let collection_info = query_collection_info(deps.as_ref(), env_at(150), "collection1".to_string(),"user".to_string()).unwrap();
+        let offset = collection_info.info.reveal_offset.unwrap();
+        assert!(offset < 10);
+
+        let token = query_token_uri(deps.as_ref(), "collection1".to_string(), 10).unwrap();
+        let number = (9 + offset) % 10 + 1;
+        assert_eq!(token.token_uri,format!("url{}.json",number));
+        assert_eq!(token.image,Some(format!("imag_url{}.png",number)));
+        for number in [0, 11].iter() {
+            assert!(query_token_uri(deps.as_ref(), "collection1".to_string(), *number).is_err());
+            assert!(query_token_traits(deps.as_ref(), "collection1".to_string(), *number).is_err());
+        }
+
+        // with the mapping public, moving the sale back open does not allow sniping tokens
+        let info = mock_info("creator", &[]);
+        execute(deps.as_mut(), env_at(150), info, ExecuteMsg::SetMintFlag { address: "collection1".to_string(), time: now + 200 }).unwrap();
+        let info = mock_info("minter2", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(20)}]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: None, proof: None };
+        let err = execute(deps.as_mut(), env_at(260), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::MintEnded {}));
     }
-}
 
+    #[test]
+    fn reveal_waits_for_reserved_and_airdrops() {
+        let mut deps = mock_cw721_dependencies();
+        let now = mock_env().block.time.seconds();
+        setup_collection(deps.as_mut(), CollectionInfo{
+            total_nft:Uint128::new(60),
+            reserved:Uint128::new(2),
+            placeholder_url:Some("hidden.json".to_string()),
+            provenance_hash:Some("a".repeat(64)),
+            ..mock_collection(now, 10)
+        });
+
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::Airdrop { nft_address: "collection1".to_string(), recipients: vec![("alice".to_string(),55)] };
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
 
-pub fn query_state_info(deps:Deps) -> StdResult<State>{
-    let state = CONFIG.load(deps.storage)?;
-    Ok(state)
-}
+        let info = mock_info("creator", &[]);
+        let err = execute(deps.as_mut(), env_at(150), info, ExecuteMsg::Reveal { address: "collection1".to_string() }).unwrap_err();
+        assert!(matches!(err, ContractError::RevealNotReady {}));
 
-pub fn query_admin_info(deps:Deps,nft_address:String) -> StdResult<Vec<AdminInfo>>{
-   let admin = ADMININFO.load(deps.storage,&nft_address)?;
-   Ok(admin)
-}
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::Airdrop { nft_address: "collection1".to_string(), recipients: vec![] };
+        execute(deps.as_mut(), env_at(150), info, msg).unwrap();
 
-pub fn query_user_info(deps:Deps, nft_address:String,address:String) -> StdResult<Uint128>{
-   let user_info = USERINFO.may_load(deps.storage, (&nft_address,&address))?;
-   if user_info == None{
-    Ok(Uint128::new(0))
-   }
-   else{
-   Ok(user_info.unwrap())
+        let info = mock_info("creator", &[]);
+        let err = execute(deps.as_mut(), env_at(150), info, ExecuteMsg::Reveal { address: "collection1".to_string() }).unwrap_err();
+        assert!(matches!(err, ContractError::RevealNotReady {}));
+
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::MintReserved { nft_address: "collection1".to_string(), recipient: "team".to_string(), quantity: 2 };
+        execute(deps.as_mut(), env_at(150), info, msg).unwrap();
+
+        let info = mock_info("creator", &[]);
+        execute(deps.as_mut(), env_at(150), info, ExecuteMsg::Reveal { address: "collection1".to_string() }).unwrap();
     }
-}
 
-pub fn query_collection_info(deps:Deps,nft_address:String,address: String) -> StdResult<CollectionInfo>{
-   let mut collection_info = COLLECTIONINFO.load(deps.storage,&nft_address)?;
-   let free_minter = FREEMINTER.may_load(deps.storage, (&nft_address,&address))?;
-   if free_minter != None{
-     collection_info.price = Uint128::new(0)
-   }
-   else {
-    if collection_info.private_mint{
-     collection_info.price = collection_info.private_price
-    } 
-    else if collection_info.public_mint{
-        collection_info.price = collection_info.public_price
-            } 
-        else {
-            collection_info.price = Uint128::new(0)
+    /// Runs complete drops of a 1000 token collection through `take_token` and its sparse
+    /// pool, then checks with a chi-squared test over token and mint position deciles that
+    /// no range of tokens is favoured at any stage of the drop.
+    #[test]
+    fn take_token_draws_uniformly() {
+        const TOKENS: u32 = 1000;
+        const DROPS: u32 = 300;
+        const BUCKETS: usize = 10;
+
+        let mut storage = MockStorage::default();
+        let mut rng = ChaChaRng::from_seed([7u8; 32]);
+        let mut counts = [[0u32; BUCKETS]; BUCKETS];
+        let bucket = |n:u32| (n as usize * BUCKETS) / TOKENS as usize;
+
+        for _ in 0..DROPS {
+            let mut seen = vec![false; TOKENS as usize];
+            for position in 0..TOKENS {
+                let remaining = TOKENS - position;
+                let token = take_token(&mut storage, "collection1", random_below(&mut rng, remaining), remaining).unwrap();
+                assert!(!seen[token as usize - 1]);
+                seen[token as usize - 1] = true;
+                counts[bucket(position)][bucket(token - 1)] += 1;
+            }
+            // a finished drop leaves nothing behind
+            assert_eq!(TOKENPOOL.prefix("collection1").range(&storage, None, None, Order::Ascending).count(), 0);
         }
-    }
-   Ok(collection_info)
-}
 
-pub fn new_entropy(info:&MessageInfo,env: &Env, seed: &[u8], entropy: &[u8]) -> [u8; 32] {
-    // 16 here represents the lengths in bytes of the block height and time.
-    let entropy_len = 16 + info.sender.to_string().len() + entropy.len();
-    let mut rng_entropy = Vec::with_capacity(entropy_len);
-    rng_entropy.extend_from_slice(&env.block.height.to_be_bytes());
-    rng_entropy.extend_from_slice(&info.sender.as_bytes());
-    rng_entropy.extend_from_slice(entropy);
+        let expected = (DROPS * TOKENS) as f64 / (BUCKETS * BUCKETS) as f64;
+        let chi_squared:f64 = counts.iter().flat_map(|row| row.iter())
+            .map(|&observed| (observed as f64 - expected).powi(2) / expected)
+            .sum();
+        // 81 degrees of freedom, p = 0.001
+        assert!(chi_squared < 126.1, "chi squared {}", chi_squared);
+    }
 
-    let mut rng = Prng::new(seed, &rng_entropy);
+    #[test]
+    fn update_hidden_collection_after_mint() {
+        let mut deps = mock_cw721_dependencies();
+        let now = mock_env().block.time.seconds();
+        let hidden = CollectionInfo{
+            placeholder_url:Some("hidden.json".to_string()),
+            provenance_hash:Some("a".repeat(64)),
+            ..mock_collection(now, 10)
+        };
+        setup_collection(deps.as_mut(), hidden.clone());
 
-    rng.rand_bytes()
-}
+        let info = mock_info("minter1", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(20)}]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: None, proof: None };
+        execute(deps.as_mut(), env_at(60), info, msg).unwrap();
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
-    use cosmwasm_std::{CosmosMsg, from_binary};
+        // the sale keeps being managed after the pool got shuffled by the mint
+        let info = mock_info("artist", &[]);
+        let msg = ExecuteMsg::UpdateCollection { members: vec![AdminInfo{
+            address:"admin2".to_string(),
+            portion:Decimal::one()
+        }],
+        nft_address: "collection1".to_string(),
+        collection: CollectionInfo{
+            phases:mock_phases(now + 100),
+            ..hidden
+        }};
+        execute(deps.as_mut(), env_at(60), info, msg).unwrap();
+
+        let collection_info = query_collection_info(deps.as_ref(), env_at(60), "collection1".to_string(),"user".to_string()).unwrap();
+        assert_eq!(collection_info.info.phases,mock_phases(now + 100));
+        assert_eq!(collection_info.info.mint_count,Uint128::new(1));
+    }
 
     #[test]
-    fn buy_token() {
-        let mut deps = mock_dependencies();
-        let instantiate_msg = InstantiateMsg {
-            owner:"creator".to_string(),
-        };
+    fn provenance_commitment() {
+        let mut deps = mock_cw721_dependencies();
+        let now = mock_env().block.time.seconds();
+        setup_collection(deps.as_mut(), mock_collection(now, 10));
+
+        let members = vec![AdminInfo{
+            address:"admin1".to_string(),
+            portion:Decimal::one()
+        }];
+
         let info = mock_info("creator", &[]);
-        let res = instantiate(deps.as_mut(), mock_env(), info, instantiate_msg).unwrap();
-        assert_eq!(0, res.messages.len());
-        
+        let msg = ExecuteMsg::UpdateCollection { members: members.clone(),
+        nft_address: "collection1".to_string(),
+        collection: CollectionInfo{
+            provenance_hash:Some("not a hash".to_string()),
+            ..mock_collection(now, 10)
+        }};
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidProvenance {}));
+
+        let provenance = crate::rand::provenance_hash(&["hash1".to_string(),"hash2".to_string()]);
         let info = mock_info("creator", &[]);
-        let msg = ExecuteMsg::AddCollection { members: vec![AdminInfo{
-            address:"admin1".to_string(),
-            portion:Decimal::from_ratio(70 as u128, 100 as u128)
-        },
-        AdminInfo{
-            address:"admin2".to_string(),
-             portion:Decimal::from_ratio(30 as u128, 100 as u128)
-        }], 
+        let msg = ExecuteMsg::UpdateCollection { members: members.clone(),
         nft_address: "collection1".to_string(),
-        collection: CollectionInfo { 
-            total_nft:Uint128::new(10),
-            check_mint:vec![1,2,3,4,5,6,7,8,9,10],
-            url :"url".to_string(),
-            image_url:"imag_url".to_string(),
-            price:Uint128::new(0),
-            denom : "ujunox".to_string(),
-            max_nft:Uint128::new(1),
-            mint_count:Uint128::new(0),
-            name:"Collection1".to_string(),
-            can_mint:true,
-            public_mint:true,
-            private_mint:false,
-            free_mint:false,
-            public_price:Uint128::new(20),
-            private_price:Uint128::new(10),
-            start_mint_time:mock_env().block.time.seconds()-10,
-            private_mint_period:50,
-            public_mint_period:50
-            } 
-        };
+        collection: CollectionInfo{
+            provenance_hash:Some(provenance.clone()),
+            ..mock_collection(now, 10)
+        }};
         execute(deps.as_mut(), mock_env(), info, msg).unwrap();
 
+        let provenance_info = query_provenance(deps.as_ref(), "collection1".to_string()).unwrap();
+        assert_eq!(provenance_info.provenance_hash,Some(provenance.clone()));
+        assert!(!provenance_info.locked);
+
+        let info = mock_info("minter1", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(20)}]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: None, proof: None };
+        execute(deps.as_mut(), env_at(60), info, msg).unwrap();
 
         let info = mock_info("creator", &[]);
-        let msg = ExecuteMsg::SetMintFlag { address: "collection1".to_string(), time: mock_env().block.time.seconds() };
+        let msg = ExecuteMsg::UpdateCollection { members: members.clone(),
+        nft_address: "collection1".to_string(),
+        collection: CollectionInfo{
+            provenance_hash:None,
+            ..mock_collection(now, 10)
+        }};
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::ProvenanceLocked {}));
+
+        // other settings stay editable
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::UpdateCollection { members,
+        nft_address: "collection1".to_string(),
+        collection: CollectionInfo{
+            provenance_hash:Some(provenance),
+            ..mock_collection(now, 5)
+        }};
         execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert!(query_provenance(deps.as_ref(), "collection1".to_string()).unwrap().locked);
+    }
 
-        let collection_info = query_collection_info(deps.as_ref(), "collection1".to_string(),"user".to_string()).unwrap();
-        assert_eq!(collection_info.price,Uint128::new(20));
-       
-        let user_info = query_user_info(deps.as_ref(), "collection1".to_string(), "user".to_string()).unwrap();
-        assert_eq!(user_info,Uint128::new(0));
+    fn minted_token_ids(res:&Response) -> Vec<String> {
+        res.messages.iter().filter_map(|message| match &message.msg {
+            CosmosMsg::Wasm(WasmMsg::Execute { msg, .. }) => match from_binary(msg).unwrap() {
+                Cw721BaseExecuteMsg::<Metadata>::Mint(mint) => Some(mint.token_id),
+                _ => None
+            },
+            _ => None
+        }).collect()
+    }
 
-        
+    #[test]
+    fn token_pool_draws_each_token_once() {
+        let mut deps = mock_cw721_dependencies();
+        let now = mock_env().block.time.seconds();
+        setup_collection(deps.as_mut(), mock_collection(now, 10));
+
+        let mut minted = vec![];
+        for quantity in [3, 1, 4, 2] {
+            let info = mock_info("minter1", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(20 * quantity as u128)}]);
+            let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: Some(quantity), proof: None };
+            let res = execute(deps.as_mut(), env_at(60), info, msg).unwrap();
+            minted.extend(minted_token_ids(&res));
+        }
+        minted.sort();
+        let mut expected:Vec<String> = (1..=10).map(|n| format!("Collection1.{}",n)).collect();
+        expected.sort();
+        assert_eq!(minted,expected);
+
+        let info = mock_info("minter1", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(20)}]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: None, proof: None };
+        let err = execute(deps.as_mut(), env_at(60), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::MintEnded {}));
+
+        // an explicit id list is drawn from just the same
+        let token_ids:Vec<u32> = (101..=110).collect();
         let info = mock_info("creator", &[]);
-        let msg = ExecuteMsg::SwitchSaleType { address: "collection1".to_string(),
-             public_mint: true, 
-             private_mint: false,
-             free_mint: false 
-        };
+        let msg = ExecuteMsg::AddCollection { members: vec![AdminInfo{
+            address:"admin1".to_string(),
+            portion:Decimal::one()
+        }],
+        nft_address: "collection2".to_string(),
+        collection: mock_collection(now, 10),
+        token_ids: Some(token_ids[1..].to_vec())};
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::WrongNumber {}));
+
+        // ids that repeat or are zero could never all be minted
+        for bad_ids in [vec![5,5,5,5,5,5,5,5,5,0], vec![0,102,103,104,105,106,107,108,109,110]].iter() {
+            let info = mock_info("creator", &[]);
+            let msg = ExecuteMsg::AddCollection { members: vec![AdminInfo{
+                address:"admin1".to_string(),
+                portion:Decimal::one()
+            }],
+            nft_address: "collection2".to_string(),
+            collection: mock_collection(now, 10),
+            token_ids: Some(bad_ids.clone())};
+            let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+            assert!(matches!(err, ContractError::WrongNumber {}));
+        }
+
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::AddCollection { members: vec![AdminInfo{
+            address:"admin1".to_string(),
+            portion:Decimal::one()
+        }],
+        nft_address: "collection2".to_string(),
+        collection: mock_collection(now, 10),
+        token_ids: Some(token_ids.clone())};
         execute(deps.as_mut(), mock_env(), info, msg).unwrap();
 
+        let info = mock_info("minter1", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(200)}]);
+        let msg = ExecuteMsg::Mint { address: "collection2".to_string(), quantity: Some(10), proof: None };
+        let res = execute(deps.as_mut(), env_at(60), info, msg).unwrap();
+        let mut minted = minted_token_ids(&res);
+        minted.sort();
+        let mut expected:Vec<String> = token_ids.iter().map(|n| format!("Collection1.{}",n)).collect();
+        expected.sort();
+        assert_eq!(minted,expected);
 
-        let info = mock_info("minter1", &[Coin{
-            denom:"ujunox".to_string(),
-            amount:Uint128::new(20)
-        }]);
-        let msg = ExecuteMsg::Mint { address: "collection1".to_string() };
-        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
-        let collection_info = query_collection_info(deps.as_ref(), "collection1".to_string(),"user".to_string()).unwrap();
-        assert_eq!(collection_info.price,Uint128::new(20));
-        
-        assert_eq!(res.messages[1].msg,CosmosMsg::Bank(BankMsg::Send {
-                to_address: "admin1".to_string(),
-                amount:vec![Coin{
-                    denom:"ujunox".to_string(),
-                    amount:Uint128::new(14)
-                }]
-        }));
-       
-        assert_eq!(res.messages[2].msg,CosmosMsg::Bank(BankMsg::Send {
-                to_address: "admin2".to_string(),
-                amount:vec![Coin{
-                    denom:"ujunox".to_string(),
-                    amount:Uint128::new(6)
-                }]
-        }));
+        // delayed reveal needs the tokens numbered 1..=total_nft
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::AddCollection { members: vec![AdminInfo{
+            address:"admin1".to_string(),
+            portion:Decimal::one()
+        }],
+        nft_address: "collection3".to_string(),
+        collection: CollectionInfo{
+            placeholder_url:Some("hidden.json".to_string()),
+            provenance_hash:Some("a".repeat(64)),
+            ..mock_collection(now, 10)
+        },
+        token_ids: Some(token_ids)};
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::WrongNumber {}));
 
         let info = mock_info("creator", &[]);
-        let msg = ExecuteMsg::AddFreeMinter { address: "collection1".to_string(), minters: vec!["minter1".to_string()] };
+        let msg = ExecuteMsg::AddCollection { members: vec![AdminInfo{
+            address:"admin1".to_string(),
+            portion:Decimal::one()
+        }],
+        nft_address: "collection3".to_string(),
+        collection: CollectionInfo{
+            placeholder_url:Some("hidden.json".to_string()),
+            provenance_hash:Some("a".repeat(64)),
+            ..mock_collection(now, 10)
+        },
+        token_ids: Some(vec![10,9,8,7,6,5,4,3,2,1])};
         execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+    }
 
+    #[test]
+    fn list_collections() {
+        let mut deps = mock_cw721_dependencies();
+        let now = mock_env().block.time.seconds();
+        setup_collection(deps.as_mut(), mock_collection(now, 10));
+
+        for nft_address in ["collection3", "collection2"] {
+            let info = mock_info("creator", &[]);
+            let msg = ExecuteMsg::AddCollection { members: vec![AdminInfo{
+                address:"admin1".to_string(),
+                portion:Decimal::one()
+            }],
+            nft_address: nft_address.to_string(),
+            collection: mock_collection(now + 100, 10),
+            token_ids: None};
+            execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        }
 
-        let info = mock_info("minter1", &[]);
-        let msg = ExecuteMsg::Mint { address: "collection1".to_string() };
-        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
-        assert_eq!(res.messages.len(),1);
+        let info = mock_info("minter1", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(40)}]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: Some(2), proof: None };
+        execute(deps.as_mut(), env_at(60), info, msg).unwrap();
+
+        let page = query_list_collections(deps.as_ref(), env_at(60), None, Some(2)).unwrap();
+        assert_eq!(page.collections,vec![
+            CollectionSummary{
+                nft_address:"collection1".to_string(),
+                name:"Collection1".to_string(),
+                mint_count:Uint128::new(2),
+                total_nft:Uint128::new(10),
+                can_mint:true,
+                status:MintStatus::Active,
+                phase:Some("public".to_string()),
+                price:Uint128::new(20),
+                denom:Some("ujunox".to_string())
+            },
+            CollectionSummary{
+                nft_address:"collection2".to_string(),
+                name:"Collection1".to_string(),
+                mint_count:Uint128::new(0),
+                total_nft:Uint128::new(10),
+                can_mint:true,
+                status:MintStatus::NotStarted,
+                phase:Some("private".to_string()),
+                price:Uint128::new(10),
+                denom:Some("ujunox".to_string())
+            }
+        ]);
 
+        let page = query_list_collections(deps.as_ref(), env_at(60), Some("collection2".to_string()), None).unwrap();
+        assert_eq!(page.collections.len(),1);
+        assert_eq!(page.collections[0].nft_address,"collection3");
+    }
+
+    #[test]
+    fn manage_allowlists() {
+        let mut deps = mock_cw721_dependencies();
+        setup_collection(deps.as_mut(), mock_collection(mock_env().block.time.seconds(), 10));
 
         let info = mock_info("creator", &[]);
-        let msg = ExecuteMsg::SwitchSaleType { address: "collection1".to_string(),
-             public_mint: false, 
-             private_mint: true,
-             free_mint: false 
-        };
-       execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        let msg = ExecuteMsg::AddWhiteUsers { address: "collection1".to_string(), white_users: vec![
+            WhiteUserInfo{address:"minter1".to_string(),count:Uint128::new(1)},
+            WhiteUserInfo{address:"minter2".to_string(),count:Uint128::new(2)},
+            WhiteUserInfo{address:"minter3".to_string(),count:Uint128::new(3)}
+        ] };
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
 
-       let collection_info = query_collection_info(deps.as_ref(), "collection1".to_string(),"user".to_string()).unwrap();
-       assert_eq!(collection_info.price,Uint128::new(10));
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::AddFreeMinter { address: "collection1".to_string(), minters: vec!["minter1".to_string(),"minter2".to_string()] };
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let white_users = query_list_white_users(deps.as_ref(), "collection1".to_string(), None, Some(2)).unwrap();
+        assert_eq!(white_users.white_users,vec![
+            WhiteUserInfo{address:"minter1".to_string(),count:Uint128::new(1)},
+            WhiteUserInfo{address:"minter2".to_string(),count:Uint128::new(2)}
+        ]);
+        let white_users = query_list_white_users(deps.as_ref(), "collection1".to_string(), Some("minter2".to_string()), None).unwrap();
+        assert_eq!(white_users.white_users,vec![WhiteUserInfo{address:"minter3".to_string(),count:Uint128::new(3)}]);
+
+        let info = mock_info("minter1", &[]);
+        let msg = ExecuteMsg::RemoveWhiteUsers { address: "collection1".to_string(), white_users: vec!["minter2".to_string()] };
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
 
         let info = mock_info("creator", &[]);
-        let msg = ExecuteMsg::AddWhiteUsers { address: "collection1".to_string(), white_users: vec![WhiteUserInfo{
-            address:"minter2".to_string(),
-            count:Uint128::new(1)
-        }] };
-       execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        let msg = ExecuteMsg::RemoveWhiteUsers { address: "collection1".to_string(), white_users: vec!["minter2".to_string()] };
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!(query_white_user_info(deps.as_ref(), "collection1".to_string(), "minter2".to_string()).unwrap(),None);
+        assert_eq!(query_white_user_info(deps.as_ref(), "collection1".to_string(), "minter3".to_string()).unwrap(),
+            Some(WhiteUserInfo{address:"minter3".to_string(),count:Uint128::new(3)}));
 
+        let info = mock_info("minter2", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(10)}]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: None, proof: None };
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::NotWhiteUsers {}));
 
+        let minters = query_list_free_minters(deps.as_ref(), "collection1".to_string(), None, None).unwrap();
+        assert_eq!(minters.minters,vec!["minter1".to_string(),"minter2".to_string()]);
 
-        let info = mock_info("minter2", &[Coin{
-            denom:"ujunox".to_string(),
-            amount:Uint128::new(10)
-        }]);
-        let msg = ExecuteMsg::Mint { address: "collection1".to_string() };
-        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
-         
-        assert_eq!(res.messages[1].msg,CosmosMsg::Bank(BankMsg::Send {
-                to_address: "admin1".to_string(),
-                amount:vec![Coin{
-                    denom:"ujunox".to_string(),
-                    amount:Uint128::new(7)
-                }]
-        }));
-       
-        assert_eq!(res.messages[2].msg,CosmosMsg::Bank(BankMsg::Send {
-                to_address: "admin2".to_string(),
-                amount:vec![Coin{
-                    denom:"ujunox".to_string(),
-                    amount:Uint128::new(3)
-                }]
-        }));
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::RemoveFreeMinters { address: "collection1".to_string(), minters: vec!["minter1".to_string()] };
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        let minters = query_list_free_minters(deps.as_ref(), "collection1".to_string(), None, None).unwrap();
+        assert_eq!(minters.minters,vec!["minter2".to_string()]);
 
-       
+        // minter1 pays again now that the free mint is gone
+        let collection_info = query_collection_info(deps.as_ref(), env_at(60), "collection1".to_string(),"minter1".to_string()).unwrap();
+        assert_eq!(collection_info.price,Uint128::new(20));
+    }
+
+    #[test]
+    fn merkle_allowlist() {
+        use crate::merkle::hash_pair;
+        use crate::rand::to_hex;
+
+        let mut deps = mock_cw721_dependencies();
+        setup_collection(deps.as_mut(), mock_collection(mock_env().block.time.seconds(), 10));
+
+        let leaf1 = leaf_hash("minter1", 2);
+        let leaf2 = leaf_hash("minter2", 1);
+        let root = to_hex(&hash_pair(&leaf1, &leaf2));
+        let proof1 = MerkleProof{allocation:Uint128::new(2),proof:vec![to_hex(&leaf2)]};
 
         let info = mock_info("creator", &[]);
-        let msg = ExecuteMsg::SwitchSaleType { address: "collection1".to_string(),
-             public_mint: false, 
-             private_mint: false,
-             free_mint: true 
-        };
-       execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        let msg = ExecuteMsg::SetMerkleRoot { address: "collection1".to_string(), phase: "private".to_string(), root: Some("root".to_string()) };
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidMerkleRoot {}));
 
-         let collection_info = query_collection_info(deps.as_ref(), "collection1".to_string(),"user".to_string()).unwrap();
-        assert_eq!(collection_info.price,Uint128::new(0));
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::SetMerkleRoot { address: "collection1".to_string(), phase: "private".to_string(), root: Some(root.clone()) };
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!(query_merkle_root(deps.as_ref(), "collection1".to_string(), "private".to_string()).unwrap(),Some(root.clone()));
+
+        // claiming more than the committed allocation fails the proof
+        let info = mock_info("minter1", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(30)}]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: Some(3), proof: Some(MerkleProof{
+            allocation:Uint128::new(3),
+            ..proof1.clone()
+        }) };
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidProof {}));
+
+        let info = mock_info("minter1", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(10)}]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: None, proof: None };
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::NotWhiteUsers {}));
+
+        let info = mock_info("minter1", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(10)}]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: None, proof: Some(proof1.clone()) };
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        // proofs are tracked per phase and leave the whitelist alone
+        assert_eq!(query_white_user_info(deps.as_ref(), "collection1".to_string(), "minter1".to_string()).unwrap(),None);
+
+        // the consumed allocation sticks even when the proof is sent again
+        let info = mock_info("minter1", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(20)}]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: Some(2), proof: Some(proof1.clone()) };
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::MintExceeded {}));
+
+        let info = mock_info("minter1", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(10)}]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: None, proof: None };
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::NotWhiteUsers {}));
+
+        let info = mock_info("minter1", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(10)}]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: None, proof: Some(proof1.clone()) };
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
 
-        let info = mock_info("minter3", &[]);
-        let msg = ExecuteMsg::Mint { address: "collection1".to_string() };
-        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
-        assert_eq!(res.messages.len(),1);
+        // a root on the public phase gates it and caps wallets at their allocation
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::SetMerkleRoot { address: "collection1".to_string(), phase: "public".to_string(), root: Some(root) };
+        execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let info = mock_info("minter3", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(20)}]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: None, proof: None };
+        let err = execute(deps.as_mut(), env_at(60), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::NotWhiteUsers {}));
+
+        let proof2 = MerkleProof{allocation:Uint128::new(1),proof:vec![to_hex(&leaf1)]};
+        let info = mock_info("minter2", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(40)}]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: Some(2), proof: Some(proof2.clone()) };
+        let err = execute(deps.as_mut(), env_at(60), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::MintExceeded {}));
+
+        let info = mock_info("minter2", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(20)}]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: None, proof: Some(proof2) };
+        execute(deps.as_mut(), env_at(60), info, msg).unwrap();
 
         let info = mock_info("creator", &[]);
-        let msg = ExecuteMsg::AddFreeMinter { address: "collection1".to_string(), minters: vec!["minter3".to_string()] };
+        let msg = ExecuteMsg::SetMerkleRoot { address: "collection1".to_string(), phase: "public".to_string(), root: None };
         execute(deps.as_mut(), mock_env(), info, msg).unwrap();
 
+        let info = mock_info("minter3", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(20)}]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: None, proof: None };
+        execute(deps.as_mut(), env_at(60), info, msg).unwrap();
+    }
 
-        let info = mock_info("minter3", &[]);
-        let msg = ExecuteMsg::Mint { address: "collection1".to_string() };
+
+    #[test]
+    fn tiered_allowlists() {
+        use crate::merkle::hash_pair;
+        use crate::rand::to_hex;
+
+        let mut deps = mock_cw721_dependencies();
+        let now = mock_env().block.time.seconds();
+        setup_collection(deps.as_mut(), CollectionInfo{
+            phases:vec![
+                SalePhase{whitelist:true, ..mock_phase("og", now, now + 50, 5)},
+                SalePhase{whitelist:true, ..mock_phase("wl", now + 50, now + 100, 10)},
+                mock_phase("public", now + 100, now + 150, 20)
+            ],
+            ..mock_collection(now, 10)
+        });
+
+        // minter1 is on both lists with different allocations, minter2 only on the og one
+        let og1 = leaf_hash("minter1", 1);
+        let og2 = leaf_hash("minter2", 3);
+        let wl1 = leaf_hash("minter1", 2);
+        let wl3 = leaf_hash("minter3", 1);
+        let og_proof = MerkleProof{allocation:Uint128::new(1),proof:vec![to_hex(&og2)]};
+        let wl_proof = MerkleProof{allocation:Uint128::new(2),proof:vec![to_hex(&wl3)]};
+        for (phase, root) in [("og", hash_pair(&og1, &og2)), ("wl", hash_pair(&wl1, &wl3))].iter() {
+            let info = mock_info("creator", &[]);
+            let msg = ExecuteMsg::SetMerkleRoot { address: "collection1".to_string(), phase: phase.to_string(), root: Some(to_hex(root)) };
+            execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        }
+
+        let info = mock_info("minter1", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(5)}]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: None, proof: Some(og_proof.clone()) };
+        execute(deps.as_mut(), env_at(10), info, msg).unwrap();
+
+        let info = mock_info("minter2", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(5)}]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: None, proof: Some(MerkleProof{allocation:Uint128::new(3),proof:vec![to_hex(&og1)]}) };
+        execute(deps.as_mut(), env_at(10), info, msg).unwrap();
+
+        // the used up og allocation does not eat into the wl one
+        let info = mock_info("minter1", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(20)}]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: Some(2), proof: Some(wl_proof) };
+        execute(deps.as_mut(), env_at(60), info, msg).unwrap();
+
+        // and what is left of an og allocation is no way into the wl phase
+        let info = mock_info("minter2", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(10)}]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: None, proof: None };
+        let err = execute(deps.as_mut(), env_at(60), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::NotWhiteUsers {}));
+
+        let info = mock_info("minter2", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(10)}]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: None, proof: Some(MerkleProof{allocation:Uint128::new(3),proof:vec![to_hex(&og1)]}) };
+        let err = execute(deps.as_mut(), env_at(60), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidProof {}));
+    }
+
+    #[test]
+    fn instantiate_collection() {
+        let mut deps = mock_cw721_dependencies();
+        let now = mock_env().block.time.seconds();
+
+        let info = mock_info("creator", &[]);
+        instantiate(deps.as_mut(), mock_env(), info, InstantiateMsg{owner:"creator".to_string()}).unwrap();
+
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::InstantiateCollection { members: vec![AdminInfo{
+            address:"admin1".to_string(),
+            portion:Decimal::one()
+        }],
+        collection: mock_collection(now, 10),
+        token_ids: None,
+        code_id: 7,
+        name: "Punks".to_string(),
+        symbol: "PUNK".to_string()};
         let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
         assert_eq!(res.messages.len(),1);
+        assert_eq!(res.messages[0].id, INSTANTIATE_CW721_REPLY_ID);
+        assert_eq!(res.messages[0].msg, CosmosMsg::Wasm(WasmMsg::Instantiate {
+            admin: Some("artist".to_string()),
+            code_id: 7,
+            msg: to_binary(&Cw721InstantiateMsg{
+                name:"Punks".to_string(),
+                symbol:"PUNK".to_string(),
+                minter:MOCK_CONTRACT_ADDR.to_string()
+            }).unwrap(),
+            funds: vec![],
+            label: "Punks".to_string()
+        }));
 
-        let collection_info = query_collection_info(deps.as_ref(), "collection1".to_string(),"user".to_string()).unwrap();
-        assert_eq!(collection_info.check_mint.len(),5);
-        
+        // nothing is registered until the cw721 address comes back
+        let collections = query_list_collections(deps.as_ref(), mock_env(), None, None).unwrap();
+        assert!(collections.collections.is_empty());
+
+        let reply_msg = Reply {
+            id: INSTANTIATE_CW721_REPLY_ID,
+            result: SubMsgResult::Ok(SubMsgExecutionResponse {
+                events: vec![Event::new("instantiate")
+                    .add_attribute("_contract_address", "collection1")
+                    .add_attribute("code_id", "7")],
+                data: None
+            })
+        };
+        let res = reply(deps.as_mut(), mock_env(), reply_msg.clone()).unwrap();
+        assert_eq!(res.attributes[1].value, "collection1");
+        assert!(PENDINGCOLLECTION.may_load(&deps.storage).unwrap().is_none());
+
+        let collection_info = query_collection_info(deps.as_ref(), env_at(60), "collection1".to_string(),"user".to_string()).unwrap();
+        assert_eq!(collection_info.info.creator,"artist");
+        assert!(collection_info.info.can_mint);
 
+        let info = mock_info("minter1", &[Coin{denom:"ujunox".to_string(),amount:Uint128::new(20)}]);
+        let msg = ExecuteMsg::Mint { address: "collection1".to_string(), quantity: None, proof: None };
+        let res = execute(deps.as_mut(), env_at(60), info, msg).unwrap();
+        assert_eq!(minted_owners(&res),vec!["minter1".to_string()]);
+
+        // a stray reply without a pending registration is rejected
+        assert!(reply(deps.as_mut(), mock_env(), reply_msg).is_err());
     }
 
+    #[test]
+    fn register_requires_minter() {
+        let mut deps = mock_cw721_dependencies();
+        let now = mock_env().block.time.seconds();
+
+        let info = mock_info("creator", &[]);
+        instantiate(deps.as_mut(), mock_env(), info, InstantiateMsg{owner:"creator".to_string()}).unwrap();
+
+        let register = ExecuteMsg::AddCollection { members: vec![AdminInfo{
+            address:"admin1".to_string(),
+            portion:Decimal::one()
+        }],
+        nft_address: "collection1".to_string(),
+        collection: mock_collection(now, 10),
+        token_ids: None};
+
+        deps.querier.minter = "artist".to_string();
+        let info = mock_info("creator", &[]);
+        let err = execute(deps.as_mut(), mock_env(), info, register.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::NotMinter {}));
+
+        deps.querier.minter = MOCK_CONTRACT_ADDR.to_string();
+        deps.querier.num_tokens = 3;
+        let info = mock_info("creator", &[]);
+        let err = execute(deps.as_mut(), mock_env(), info, register.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::CollectionNotEmpty {}));
+
+        deps.querier.num_tokens = 0;
+        let info = mock_info("creator", &[]);
+        execute(deps.as_mut(), mock_env(), info, register).unwrap();
+    }
 }
@@ -12,16 +12,63 @@ pub enum ContractError {
     #[error("Less than zero")]
     ZeorError {},
 
+    #[error("Platform fee cannot exceed 10000 basis points")]
+    InvalidFee {},
+
+    #[error("Invalid template: {template}")]
+    InvalidTemplate {
+        template:String
+    },
+
+    #[error("Token metadata cannot change once minting started")]
+    MetadataLocked {},
+
+    #[error("Royalty percentage cannot exceed 100")]
+    InvalidRoyalty {},
+
+    #[error("Nothing to withdraw")]
+    NothingToWithdraw {},
+
     #[error("Collection Not Found")]
     CollectionNotFound {},
 
+    #[error("Collection already exists")]
+    CollectionExists {},
+
+    #[error("Collection is not approved for minting")]
+    CollectionNotApproved {},
+
+    #[error("Collection already minted tokens")]
+    CollectionLive {},
+
+    #[error("Only the creator or admin of the cw721 contract can register it")]
+    NotCollectionOwner {},
+
+    #[error("This contract is not the minter of the cw721 contract")]
+    NotMinter {},
+
+    #[error("The cw721 contract already has tokens")]
+    CollectionNotEmpty {},
+
      #[error("Not white users")]
-    NOTWHITEUSERS {},
+    NotWhiteUsers {},
+
+    #[error("Invalid merkle proof")]
+    InvalidProof {},
+
+    #[error("Merkle root must be a hex encoded SHA-256 digest")]
+    InvalidMerkleRoot {},
 
     
     #[error("Not enough funds")]
     Notenough{},
 
+    #[error("Wrong denom: expected {expected}, received {received}")]
+    WrongDenom{
+        expected:String,
+        received:String
+    },
+
     
     #[error("Mint is ended")]
     MintEnded{},
@@ -38,6 +85,37 @@ pub enum ContractError {
     #[error("Mint is not started yet")]
     MintNotStarted{},
 
+    #[error("Sale phase {name} is sold out")]
+    PhaseSoldOut{
+        name:String
+    },
+
+    #[error("Sale phase not found")]
+    PhaseNotFound{},
+
+    #[error("Invalid sale phases: {reason}")]
+    InvalidPhases{
+        reason:String
+    },
+
+    #[error("Delayed reveal needs a provenance hash")]
+    NoProvenance{},
+
+    #[error("Reveal settings can not change after minting started")]
+    RevealLocked{},
+
+    #[error("Provenance hash must be a hex encoded SHA-256 digest")]
+    InvalidProvenance{},
+
+    #[error("Provenance hash can not change after minting started")]
+    ProvenanceLocked{},
+
+    #[error("Collection can not be revealed before it is sold out or the sale ended, and its reserved and airdropped tokens are minted")]
+    RevealNotReady{},
+
+    #[error("Collection is already revealed")]
+    AlreadyRevealed{},
+
     #[error("Escrow expired (end_height {end_height:?} end_time {end_time:?})")]
     Expired {
         end_height: Option<u64>,
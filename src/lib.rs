@@ -1,5 +1,8 @@
+#![allow(non_snake_case)]
+
 pub mod contract;
 mod error;
 pub mod msg;
 pub mod state;
-pub mod rand;
\ No newline at end of file
+pub mod merkle;
+pub mod rand;
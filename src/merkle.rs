@@ -0,0 +1,104 @@
+use crate::rand::{sha_256, to_hex};
+
+/// Leaf of an allowlist tree: the SHA-256 of `"<address>:<allocation>"`.
+pub fn leaf_hash(address: &str, allocation: u128) -> [u8; 32] {
+    sha_256(format!("{}:{}", address, allocation).as_bytes())
+}
+
+/// Hashes a pair of nodes in sorted order, so proofs do not need to carry
+/// whether each sibling sits on the left or the right.
+pub fn hash_pair(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    if a <= b {
+        sha_256(&[a.as_ref(), b.as_ref()].concat())
+    } else {
+        sha_256(&[b.as_ref(), a.as_ref()].concat())
+    }
+}
+
+/// Whether `proof`, a list of hex encoded sibling hashes from the leaf up,
+/// links `leaf` to the hex encoded `root`.
+pub fn verify_proof(root: &str, proof: &[String], leaf: [u8; 32]) -> bool {
+    let mut hash = leaf;
+    for sibling in proof {
+        match from_hex(sibling) {
+            Some(sibling) => hash = hash_pair(&hash, &sibling),
+            None => return false,
+        }
+    }
+    to_hex(&hash) == root
+}
+
+/// Whether `value` is a lowercase hex encoded SHA-256 digest.
+pub fn is_hex_digest(value: &str) -> bool {
+    value.len() == 64 && value.chars().all(|c| matches!(c, '0'..='9' | 'a'..='f'))
+}
+
+fn from_hex(value: &str) -> Option<[u8; 32]> {
+    if !is_hex_digest(value) {
+        return None;
+    }
+    let mut bytes = [0u8; 32];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&value[2 * i..2 * i + 2], 16).ok()?;
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds every level of a tree, duplicating the last node of odd levels.
+    fn tree(leaves: Vec<[u8; 32]>) -> Vec<Vec<[u8; 32]>> {
+        let mut levels = vec![leaves];
+        while levels.last().unwrap().len() > 1 {
+            let level = levels.last().unwrap();
+            let next = level
+                .chunks(2)
+                .map(|pair| hash_pair(&pair[0], pair.get(1).unwrap_or(&pair[0])))
+                .collect();
+            levels.push(next);
+        }
+        levels
+    }
+
+    fn proof(levels: &[Vec<[u8; 32]>], mut index: usize) -> Vec<String> {
+        let mut proof = vec![];
+        for level in &levels[..levels.len() - 1] {
+            let sibling = level.get(index ^ 1).unwrap_or(&level[index]);
+            proof.push(to_hex(sibling));
+            index /= 2;
+        }
+        proof
+    }
+
+    #[test]
+    fn test_verify_proof() {
+        let users = [("addr1", 1), ("addr2", 2), ("addr3", 3), ("addr4", 1), ("addr5", 5)];
+        let levels = tree(users.iter().map(|(a, n)| leaf_hash(a, *n)).collect());
+        let root = to_hex(&levels.last().unwrap()[0]);
+
+        for (index, (address, allocation)) in users.iter().enumerate() {
+            let proof = proof(&levels, index);
+            assert!(verify_proof(&root, &proof, leaf_hash(address, *allocation)));
+            // claiming a larger allocation or someone else's proof fails
+            assert!(!verify_proof(&root, &proof, leaf_hash(address, allocation + 1)));
+            assert!(!verify_proof(&root, &proof, leaf_hash("addr6", *allocation)));
+        }
+
+        assert!(!verify_proof(&root, &["zz".to_string()], leaf_hash("addr1", 1)));
+    }
+
+    #[test]
+    fn test_single_leaf_tree() {
+        let leaf = leaf_hash("addr1", 4);
+        assert!(verify_proof(&to_hex(&leaf), &[], leaf));
+    }
+
+    #[test]
+    fn test_is_hex_digest() {
+        assert!(is_hex_digest(&"0a".repeat(32)));
+        assert!(!is_hex_digest(&"0A".repeat(32)));
+        assert!(!is_hex_digest(&"0a".repeat(31)));
+    }
+}
@@ -1,8 +1,8 @@
-use cosmwasm_std::{ Uint128};
+use cosmwasm_std::{ Coin, Uint128};
 use schemars::{JsonSchema};
 use serde::{Deserialize, Serialize};
 
-use crate::state::{AdminInfo, CollectionInfo};
+use crate::state::{AdminInfo, CollectionInfo, MintStatus, SalePhase};
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct InstantiateMsg {
@@ -12,14 +12,31 @@ pub struct InstantiateMsg {
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ExecuteMsg {
-    Mint{address:String},
+    Mint{address:String,quantity:Option<u32>,proof:Option<MerkleProof>},
     ChangeOwner {address:String},
-    AddCollection{members:Vec<AdminInfo>,nft_address:String,collection:CollectionInfo},
+    AddCollection{members:Vec<AdminInfo>,nft_address:String,collection:CollectionInfo,token_ids:Option<Vec<u32>>},
+    /// Instantiates cw721-base from `code_id` with this contract as minter and registers it
+    InstantiateCollection{members:Vec<AdminInfo>,collection:CollectionInfo,token_ids:Option<Vec<u32>>,code_id:u64,name:String,symbol:String},
     UpdateCollection{members:Vec<AdminInfo>,nft_address:String,collection:CollectionInfo},
     SetMintFlag{address:String,time:u64},
     AddFreeMinter{address:String,minters:Vec<String>},
-    SwitchSaleType{address:String,public_mint:bool,private_mint:bool,free_mint:bool},
-    AddWhiteUsers{address:String,white_users:Vec<WhiteUserInfo>}
+    AddWhiteUsers{address:String,white_users:Vec<WhiteUserInfo>},
+    Reveal{address:String},
+    RemoveWhiteUsers{address:String,white_users:Vec<String>},
+    RemoveFreeMinters{address:String,minters:Vec<String>},
+    SetMerkleRoot{address:String,phase:String,root:Option<String>},
+    MintReserved{nft_address:String,recipient:String,quantity:u32},
+    Airdrop{nft_address:String,recipients:Vec<(String,u32)>},
+    SetListingFee{fee:Option<Coin>},
+    SetTreasury{address:String},
+    SetPlatformFee{fee_bps:u64},
+    ApproveCollection{nft_address:String},
+    RejectCollection{nft_address:String},
+    DelistCollection{nft_address:String},
+    Withdraw{nft_address:String},
+    WithdrawAll{},
+    SweepDust{},
+    UploadTraits{nft_address:String,traits:Vec<(u32,Vec<Trait>)>}
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -28,13 +45,43 @@ pub enum QueryMsg {
       GetStateInfo{},
       GetCollectionInfo{nft_address:String,address:String},
       GetUserInfo{nft_address:String,address:String},
-      GetAdminInfo{nft_address:String}
+      GetAdminInfo{nft_address:String},
+      GetTokenUri{nft_address:String,token_number:u32},
+      GetProvenance{nft_address:String},
+      ListCollections{start_after:Option<String>,limit:Option<u32>},
+      GetWhiteUserInfo{nft_address:String,address:String},
+      ListWhiteUsers{nft_address:String,start_after:Option<String>,limit:Option<u32>},
+      ListFreeMinters{nft_address:String,start_after:Option<String>,limit:Option<u32>},
+      GetMerkleRoot{nft_address:String,phase:String},
+      GetPendingPayouts{address:String,nft_address:Option<String>},
+      GetBalances{},
+      GetTokenTraits{nft_address:String,token_number:u32}
     }
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+#[serde(rename_all = "snake_case")]
+pub struct Trait {
+    pub display_type: Option<String>,
+    pub trait_type: String,
+    pub value: String,
+}
+
+/// Token extension in the cw721-metadata-onchain shape, extended with the cw2981 royalty fields.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
 #[serde(rename_all = "snake_case")]
-pub struct Image {   
+pub struct Metadata {   
     pub image: Option<String>,
+    pub image_data: Option<String>,
+    pub external_url: Option<String>,
+    pub description: Option<String>,
+    pub name: Option<String>,
+    pub attributes: Option<Vec<Trait>>,
+    pub background_color: Option<String>,
+    pub animation_url: Option<String>,
+    pub youtube_url: Option<String>,
+    /// cw2981 royalty fields, present when the collection sets a royalty
+    pub royalty_percentage: Option<u64>,
+    pub royalty_payment_address: Option<String>,
 }
 
 
@@ -45,3 +92,107 @@ pub struct WhiteUserInfo {
     pub address: String,
     pub count:Uint128
 }
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct CollectionInfoResponse {
+    #[serde(flatten)]
+    pub info: CollectionInfo,
+    pub status: MintStatus,
+    /// The running phase, or the next one while the sale has not started
+    pub phase: Option<SalePhase>,
+    /// Seconds until the current phase ends, or until the next one starts when not started yet
+    pub phase_remaining: Option<u64>,
+    /// Unit price the queried address pays in `phase`
+    pub price: Uint128
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct TokenUriResponse {
+    pub token_uri: String,
+    pub image: Option<String>
+}
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct ProvenanceResponse {
+    pub provenance_hash: Option<String>,
+    /// Whether the hash can no longer change, i.e. minting has started
+    pub locked: bool,
+    pub reveal_offset: Option<u32>
+}
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct CollectionSummary {
+    pub nft_address: String,
+    pub name: String,
+    pub mint_count: Uint128,
+    pub total_nft: Uint128,
+    pub can_mint: bool,
+    pub status: MintStatus,
+    pub phase: Option<String>,
+    pub price: Uint128,
+    pub denom: Option<String>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct CollectionsResponse {
+    pub collections: Vec<CollectionSummary>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct WhiteUsersResponse {
+    pub white_users: Vec<WhiteUserInfo>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct FreeMintersResponse {
+    pub minters: Vec<String>
+}
+
+/// Allowlist membership proven against a collection's merkle root.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct MerkleProof {
+    pub allocation: Uint128,
+    /// Hex encoded sibling hashes from the leaf up to the root
+    pub proof: Vec<String>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct PendingPayout {
+    pub nft_address: String,
+    pub denom: String,
+    pub amount: Uint128
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct PendingPayoutsResponse {
+    pub payouts: Vec<PendingPayout>
+}
+
+/// Contract balance in one denom, split into what admins can withdraw and the unclaimed rest.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct BalanceInfo {
+    pub denom: String,
+    pub balance: Uint128,
+    pub owed: Uint128,
+    pub dust: Uint128
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct BalancesResponse {
+    pub balances: Vec<BalanceInfo>
+}
@@ -13,6 +13,31 @@ pub fn sha_256(data: &[u8]) -> [u8; 32] {
     result
 }
 
+/// Lowercase hex encoding of a digest, the form provenance hashes are stored in.
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Provenance hash of a collection: the SHA-256 of the hex image hashes
+/// concatenated in token order.
+pub fn provenance_hash(image_hashes: &[String]) -> String {
+    to_hex(&sha_256(image_hashes.concat().as_bytes()))
+}
+
+/// Returns a uniformly distributed number in `0..bound`.
+///
+/// Draws that fall into the incomplete last block of `u32` values are rejected,
+/// since `next_u32() % bound` would otherwise favour the low numbers.
+pub fn random_below<R: RngCore>(rng: &mut R, bound: u32) -> u32 {
+    let threshold = bound.wrapping_neg() % bound;
+    loop {
+        let r = rng.next_u32();
+        if r >= threshold {
+            return r % bound;
+        }
+    }
+}
+
 pub struct Prng {
     rng: ChaChaRng,
 }
@@ -22,8 +47,8 @@ impl Prng {
         let mut hasher = Sha256::new();
 
         // write input message
-        hasher.update(&seed);
-        hasher.update(&entropy);
+        hasher.update(seed);
+        hasher.update(entropy);
         let hash = hasher.finalize();
 
         let mut hash_bytes = [0u8; 32];
@@ -72,4 +97,81 @@ mod tests {
         assert_eq!(r3, rng.rand_bytes());
         assert_eq!(r4, rng.rand_bytes());
     }
+
+    #[test]
+    fn test_random_below_in_range() {
+        let mut rng = ChaChaRng::from_seed([7u8; 32]);
+        for bound in 1..100 {
+            for _ in 0..100 {
+                assert!(random_below(&mut rng, bound) < bound);
+            }
+        }
+        assert_eq!(random_below(&mut rng, 1), 0);
+    }
+
+    /// Simulates many complete drops of a small collection, drawing and removing
+    /// token numbers from a list, and checks with a chi-squared test that every
+    /// token is equally likely at every position. The contract's own pool is
+    /// covered by `take_token_draws_uniformly` in the contract tests.
+    #[test]
+    fn test_simulated_drop_is_uniform() {
+        const TOKENS: usize = 10;
+        const DROPS: usize = 20_000;
+
+        let mut rng = ChaChaRng::from_seed([42u8; 32]);
+        let mut counts = [[0u32; TOKENS]; TOKENS];
+
+        for _ in 0..DROPS {
+            let mut remaining: Vec<usize> = (0..TOKENS).collect();
+            for position in counts.iter_mut() {
+                let index = random_below(&mut rng, remaining.len() as u32) as usize;
+                position[remaining.remove(index)] += 1;
+            }
+            assert!(remaining.is_empty());
+        }
+
+        let expected = (DROPS / TOKENS) as f64;
+        for position in counts.iter() {
+            let chi_squared: f64 = position
+                .iter()
+                .map(|&observed| (observed as f64 - expected).powi(2) / expected)
+                .sum();
+            // 9 degrees of freedom, p = 0.001
+            assert!(chi_squared < 27.88, "chi squared {}", chi_squared);
+        }
+    }
+
+    /// With a bound of three quarters of `u32::MAX`, plain modulo would return the
+    /// first third of the range twice as often and put 62.5% of draws in the lower
+    /// half; rejection sampling keeps both halves even.
+    #[test]
+    fn test_random_below_is_unbiased() {
+        let bound = (u32::MAX / 4) * 3;
+        let mut rng = ChaChaRng::from_seed([1u8; 32]);
+
+        let draws = 100_000;
+        let low = (0..draws)
+            .filter(|_| random_below(&mut rng, bound) < bound / 2)
+            .count();
+
+        let ratio = low as f64 / draws as f64;
+        assert!((ratio - 0.5).abs() < 0.01, "ratio {}", ratio);
+    }
+
+    #[test]
+    fn test_provenance_hash() {
+        let image_hashes = vec![to_hex(&sha_256(b"image1")), to_hex(&sha_256(b"image2"))];
+        assert_eq!(
+            provenance_hash(&image_hashes),
+            to_hex(&sha_256([image_hashes[0].as_str(), image_hashes[1].as_str()].concat().as_bytes()))
+        );
+        assert_eq!(
+            provenance_hash(&[]),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_ne!(
+            provenance_hash(&image_hashes),
+            provenance_hash(&[image_hashes[1].clone(), image_hashes[0].clone()])
+        );
+    }
 }
@@ -1,6 +1,8 @@
-use cosmwasm_std::{Uint128, Decimal};
+use cosmwasm_std::{Coin, Uint128, Decimal};
 use cw_storage_plus::{Map,Item};
 use schemars::JsonSchema;
+
+use crate::msg::Trait;
 use serde::{Deserialize, Serialize};
 
 
@@ -10,11 +12,50 @@ pub const USERINFO: Map<(&str,&str), Uint128> = Map::new("user_info");
 pub const COLLECTIONINFO : Map<&str, CollectionInfo> = Map::new("collection_info");
 pub const FREEMINTER:Map<(&str,&str),bool>  = Map::new("config_free_minter");
 pub const WHITEUSERS:Map<(&str,&str),Uint128>  = Map::new("config_white_user_info");
+pub const ENTROPY:Map<&str,Vec<u8>>  = Map::new("collection_entropy");
+/// Sparse Fisher–Yates pool of the tokens left to mint: index `i` holds token `i + 1`
+/// unless an entry says otherwise.
+pub const TOKENPOOL:Map<(&str,u32),u32>  = Map::new("token_pool");
+/// Airdrop recipients still waiting for their tokens, keyed by their position in the queue
+pub const AIRDROPS:Map<(&str,u32),(String,u32)>  = Map::new("airdrop_recipients");
+pub const AIRDROPQUEUE:Map<&str,AirdropQueue>  = Map::new("airdrop_queue");
+/// Mint proceeds waiting to be withdrawn, keyed by (admin, collection, denom)
+pub const PAYOUTS:Map<(&str,&str,&str),Uint128>  = Map::new("pending_payouts");
+/// Sum of `PAYOUTS` per denom, the part of the contract balance owed to admins
+pub const OWEDPAYOUTS:Map<&str,Uint128>  = Map::new("owed_payouts");
+/// Uploaded attributes per metadata number, embedded in the token extension at mint
+pub const TOKENTRAITS:Map<(&str,u32),Vec<Trait>>  = Map::new("token_traits");
+pub const PHASEUSERINFO:Map<(&str,&str,&str),Uint128>  = Map::new("phase_user_info");
+pub const PHASEMINTCOUNT:Map<(&str,&str),Uint128>  = Map::new("phase_mint_count");
+/// Registration waiting for the cw721 instantiated by `InstantiateCollection` to reply
+pub const PENDINGCOLLECTION:Item<PendingCollection>  = Item::new("pending_collection");
 
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct State {
     pub owner:String,
+    /// Receives platform fees, the owner unless set otherwise
+    pub treasury:String,
+    /// Charged when anyone but the owner registers a collection
+    pub listing_fee:Option<Coin>,
+    /// Share of every paid mint sent to the treasury, in basis points
+    pub platform_fee_bps:u64,
+}
+
+/// Positions of the first and one past the last queued recipient, and the tokens they are owed
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AirdropQueue {
+    pub head:u32,
+    pub tail:u32,
+    pub pending:Uint128
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PendingCollection {
+    pub members:Vec<AdminInfo>,
+    pub collection:CollectionInfo,
+    pub token_ids:Option<Vec<u32>>,
+    pub approved:bool
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -29,21 +70,83 @@ pub struct AdminInfo {
 pub struct CollectionInfo {
     pub total_nft:Uint128,
     pub mint_count : Uint128,
-    pub check_mint:Vec<u32>,
     pub url :String,
     pub image_url:String,
-    pub price:Uint128,
-    pub denom:String,
     pub max_nft:Uint128,
     pub name:String,
+    /// Unset while a self-registered collection awaits approval and once it is delisted
     pub can_mint:bool,
-    pub public_mint:bool,
-    pub private_mint:bool,
-    pub free_mint:bool,
-    pub public_price:Uint128,
-    pub private_price:Uint128,
-    pub start_mint_time:u64,
-    pub private_mint_period:u64,
-    pub public_mint_period:u64
+    /// Manages the collection's phases, allowlists and payouts next to the contract owner
+    pub creator:String,
+    /// Send each admin their share on every mint instead of accruing it for `Withdraw`
+    pub auto_payout:bool,
+    /// How token ids and metadata locations are built, `name.n`, `url n.json` and `image_url n.png` when unset
+    pub templates:Option<TokenTemplates>,
+    /// On-chain metadata computed for every minted token
+    pub metadata:Option<MetadataTemplate>,
+    /// Royalty written into every minted token for marketplaces to honour
+    pub royalty:Option<Royalty>,
+    /// Tokens held back from the sale, only mintable through `MintReserved`
+    pub reserved:Uint128,
+    pub reserved_count:Uint128,
+    /// Sale phases ordered by start time, at most one of them active at any time
+    pub phases:Vec<SalePhase>,
+    /// When set, tokens are minted with this uri, a template that may use `{n}` for the token
+    /// number, and their metadata is only assigned by `Reveal`. `GetTokenUri` resolves it after that
+    pub placeholder_url:Option<String>,
+    pub provenance_hash:Option<String>,
+    /// Shift applied to token numbers when resolving metadata, set once by `Reveal`
+    pub reveal_offset:Option<u32>
+}
+
+/// Templates for token ids and metadata locations. `{name}`, `{url}` and `{image_url}` stand
+/// for the collection settings and `{n}` for the token number, zero padded to `padding` digits.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TokenTemplates {
+    pub token_id:String,
+    pub token_uri:String,
+    pub image:Option<String>,
+    pub padding:Option<u32>
 }
 
+/// Metadata fields filled in for each token, with `{n}` replaced by the token number.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+pub struct MetadataTemplate {
+    pub name:Option<String>,
+    pub description:Option<String>,
+    pub external_url:Option<String>,
+    pub animation_url:Option<String>,
+    pub background_color:Option<String>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Royalty {
+    /// Percentage of secondary sales, from 0 to 100
+    pub percentage:u64,
+    pub payment_address:String
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SalePhase {
+    pub name:String,
+    pub start_time:u64,
+    pub end_time:u64,
+    pub price:Uint128,
+    pub denom:String,
+    /// Mints allowed per wallet during this phase, on top of the collection wide `max_nft`
+    pub max_per_wallet:Option<Uint128>,
+    /// Restricts the phase to its allowlist: the merkle root when set, else `AddWhiteUsers`
+    pub whitelist:bool,
+    /// Restricts the phase to addresses proving an allocation against this root, counted per phase
+    pub merkle_root:Option<String>,
+    /// Tokens this phase may mint in total
+    pub max_supply:Option<Uint128>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MintStatus {
+    NotStarted,
+    Active,
+    Ended
+}